@@ -0,0 +1,113 @@
+// Ways of combining values for MeshMap::roll_up
+
+use super::Aggregate;
+
+/// Sum of the values
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sum;
+
+/// Arithmetic mean of the values
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mean;
+
+/// Smallest value
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Min;
+
+/// Largest value
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Max;
+
+/// Number of values
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Count;
+
+impl<V: Copy + std::ops::Add<Output = V>> Aggregate<V> for Sum {
+    type State = V;
+    type Output = V;
+
+    fn init(&self, value: &V) -> V {
+        *value
+    }
+
+    fn merge(&self, state: &mut V, other: V) {
+        *state = *state + other;
+    }
+
+    fn finish(&self, state: &V) -> V {
+        *state
+    }
+}
+
+impl<V: Copy + Into<f64>> Aggregate<V> for Mean {
+    type State = (f64, usize);
+    type Output = f64;
+
+    fn init(&self, value: &V) -> (f64, usize) {
+        ((*value).into(), 1)
+    }
+
+    fn merge(&self, state: &mut (f64, usize), other: (f64, usize)) {
+        state.0 += other.0;
+        state.1 += other.1;
+    }
+
+    fn finish(&self, state: &(f64, usize)) -> f64 {
+        state.0 / state.1 as f64
+    }
+}
+
+impl<V: Copy + PartialOrd> Aggregate<V> for Min {
+    type State = V;
+    type Output = V;
+
+    fn init(&self, value: &V) -> V {
+        *value
+    }
+
+    fn merge(&self, state: &mut V, other: V) {
+        if other < *state {
+            *state = other;
+        }
+    }
+
+    fn finish(&self, state: &V) -> V {
+        *state
+    }
+}
+
+impl<V: Copy + PartialOrd> Aggregate<V> for Max {
+    type State = V;
+    type Output = V;
+
+    fn init(&self, value: &V) -> V {
+        *value
+    }
+
+    fn merge(&self, state: &mut V, other: V) {
+        if other > *state {
+            *state = other;
+        }
+    }
+
+    fn finish(&self, state: &V) -> V {
+        *state
+    }
+}
+
+impl<V> Aggregate<V> for Count {
+    type State = usize;
+    type Output = usize;
+
+    fn init(&self, _value: &V) -> usize {
+        1
+    }
+
+    fn merge(&self, state: &mut usize, other: usize) {
+        *state += other;
+    }
+
+    fn finish(&self, state: &usize) -> usize {
+        *state
+    }
+}
@@ -0,0 +1,464 @@
+// Compressed bitmap sets of codes of a single level (roaring-bitmap style)
+
+use super::*;
+use std::marker::PhantomData;
+
+/// Containers holding at most this many values are kept as sorted arrays
+const ARRAY_LIMIT: usize = 4096;
+
+/// A set of codes of level `L` stored as a compressed bitmap of their ordinals
+///
+/// Ordinals (see [`MeshLevel::ordinal`]) are split into chunks of 65536 by their upper 16 bits.
+/// Sparse chunks are stored as sorted arrays and dense chunks as 8 KiB bitmaps, so dense
+/// regions such as the whole land of Japan take about one bit per code.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MeshBitmap<L> {
+    /// Containers sorted by their keys (upper 16 bits of the ordinals); never empty
+    containers: Vec<(u16, Container)>,
+    _level: PhantomData<L>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Container {
+    Array(Vec<u16>),
+    Bitmap(Box<[u64; 1024]>, usize),
+}
+
+impl Container {
+    fn len(&self) -> usize {
+        match self {
+            Container::Array(a) => a.len(),
+            Container::Bitmap(_, len) => *len,
+        }
+    }
+
+    fn contains(&self, v: u16) -> bool {
+        match self {
+            Container::Array(a) => a.binary_search(&v).is_ok(),
+            Container::Bitmap(w, _) => w[v as usize >> 6] >> (v & 63) & 1 == 1,
+        }
+    }
+
+    fn insert(&mut self, v: u16) -> bool {
+        match self {
+            Container::Array(a) => match a.binary_search(&v) {
+                Ok(_) => return false,
+                Err(i) if a.len() < ARRAY_LIMIT => a.insert(i, v),
+                Err(_) => {
+                    let mut words = self.to_words();
+                    words[v as usize >> 6] |= 1 << (v & 63);
+                    *self = Container::Bitmap(words, ARRAY_LIMIT + 1);
+                }
+            },
+            Container::Bitmap(w, len) => {
+                let word = &mut w[v as usize >> 6];
+                if *word >> (v & 63) & 1 == 1 {
+                    return false;
+                }
+                *word |= 1 << (v & 63);
+                *len += 1;
+            }
+        }
+        true
+    }
+
+    fn remove(&mut self, v: u16) -> bool {
+        match self {
+            Container::Array(a) => match a.binary_search(&v) {
+                Ok(i) => {
+                    a.remove(i);
+                }
+                Err(_) => return false,
+            },
+            Container::Bitmap(w, len) => {
+                let word = &mut w[v as usize >> 6];
+                if *word >> (v & 63) & 1 == 0 {
+                    return false;
+                }
+                *word &= !(1 << (v & 63));
+                *len -= 1;
+                if *len <= ARRAY_LIMIT {
+                    *self = Container::Array(self.iter().collect());
+                }
+            }
+        }
+        true
+    }
+
+    fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        let (array, words) = match self {
+            Container::Array(a) => (&a[..], &[][..]),
+            Container::Bitmap(w, _) => (&[][..], &w[..]),
+        };
+        array
+            .iter()
+            .copied()
+            .chain(words.iter().enumerate().flat_map(|(i, &w)| {
+                let mut w = w;
+                std::iter::from_fn(move || {
+                    (w != 0).then(|| {
+                        let bit = w.trailing_zeros() as usize;
+                        w &= w - 1;
+                        (i * 64 + bit) as u16
+                    })
+                })
+            }))
+    }
+
+    fn to_words(&self) -> Box<[u64; 1024]> {
+        match self {
+            Container::Array(a) => {
+                let mut words = Box::new([0u64; 1024]);
+                for &v in a {
+                    words[v as usize >> 6] |= 1 << (v & 63);
+                }
+                words
+            }
+            Container::Bitmap(w, _) => w.clone(),
+        }
+    }
+
+    /// Builds a container of the appropriate kind, or None if empty
+    fn from_words(words: Box<[u64; 1024]>) -> Option<Self> {
+        let len = words.iter().map(|w| w.count_ones() as usize).sum();
+        match len {
+            0 => None,
+            _ if len <= ARRAY_LIMIT => Some(Container::Array(
+                Container::Bitmap(words, len).iter().collect(),
+            )),
+            _ => Some(Container::Bitmap(words, len)),
+        }
+    }
+
+    fn from_array(array: Vec<u16>) -> Option<Self> {
+        match array.len() {
+            0 => None,
+            len if len <= ARRAY_LIMIT => Some(Container::Array(array)),
+            _ => Container::from_words(Container::Array(array).to_words()),
+        }
+    }
+
+    /// Combines two containers with a set operation
+    fn combine(&self, other: &Self, op: SetOp) -> Option<Self> {
+        match (self, other) {
+            (Container::Array(a), Container::Array(b)) => Container::from_array(merge(a, b, op)),
+            (Container::Array(a), _) if op == SetOp::And || op == SetOp::AndNot => {
+                let keep = |v: &&u16| other.contains(**v) == (op == SetOp::And);
+                Container::from_array(a.iter().filter(keep).copied().collect())
+            }
+            (_, Container::Array(b)) if op == SetOp::And => {
+                Container::from_array(b.iter().filter(|v| self.contains(**v)).copied().collect())
+            }
+            _ => {
+                let mut words = self.to_words();
+                let other = other.to_words();
+                for (w, o) in words.iter_mut().zip(other.iter()) {
+                    *w = op.apply(*w, *o);
+                }
+                Container::from_words(words)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetOp {
+    Or,
+    And,
+    AndNot,
+    Xor,
+}
+
+impl SetOp {
+    fn apply(self, a: u64, b: u64) -> u64 {
+        match self {
+            SetOp::Or => a | b,
+            SetOp::And => a & b,
+            SetOp::AndNot => a & !b,
+            SetOp::Xor => a ^ b,
+        }
+    }
+
+    /// Returns whether a value is in the result, given whether it is in each operand
+    fn keeps(self, in_a: bool, in_b: bool) -> bool {
+        self.apply(in_a as u64, in_b as u64) == 1
+    }
+}
+
+/// Merges two sorted arrays with a set operation
+fn merge(a: &[u16], b: &[u16], op: SetOp) -> Vec<u16> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let (v, in_a, in_b) = match (a.get(i), b.get(j)) {
+            (Some(&x), Some(&y)) if x == y => (x, true, true),
+            (Some(&x), Some(&y)) if x < y => (x, true, false),
+            (Some(&x), None) => (x, true, false),
+            (_, Some(&y)) => (y, false, true),
+            (None, None) => unreachable!(),
+        };
+        i += in_a as usize;
+        j += in_b as usize;
+        if op.keeps(in_a, in_b) {
+            out.push(v);
+        }
+    }
+    out
+}
+
+impl<L: MeshLevel> MeshBitmap<L> {
+    pub fn new() -> Self {
+        Self {
+            containers: Vec::new(),
+            _level: PhantomData,
+        }
+    }
+
+    /// Returns the number of codes in the set
+    pub fn len(&self) -> usize {
+        self.containers.iter().map(|(_, c)| c.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.containers.is_empty()
+    }
+
+    /// Adds the code, returning false if it was already present
+    pub fn insert(&mut self, code: L) -> bool {
+        let (key, low) = split_ordinal(code.ordinal());
+        match self.containers.binary_search_by_key(&key, |(k, _)| *k) {
+            Ok(i) => self.containers[i].1.insert(low),
+            Err(i) => {
+                self.containers
+                    .insert(i, (key, Container::Array(vec![low])));
+                true
+            }
+        }
+    }
+
+    /// Removes the code, returning false if it was not present
+    pub fn remove(&mut self, code: L) -> bool {
+        let (key, low) = split_ordinal(code.ordinal());
+        let Ok(i) = self.containers.binary_search_by_key(&key, |(k, _)| *k) else {
+            return false;
+        };
+        let removed = self.containers[i].1.remove(low);
+        if self.containers[i].1.len() == 0 {
+            self.containers.remove(i);
+        }
+        removed
+    }
+
+    pub fn contains(&self, code: L) -> bool {
+        let (key, low) = split_ordinal(code.ordinal());
+        self.containers
+            .binary_search_by_key(&key, |(k, _)| *k)
+            .is_ok_and(|i| self.containers[i].1.contains(low))
+    }
+
+    /// Returns the codes in code order
+    pub fn iter(&self) -> impl Iterator<Item = L> + '_ {
+        self.containers.iter().flat_map(|(key, c)| {
+            c.iter().map(move |low| {
+                L::from_ordinal((*key as u32) << 16 | low as u32)
+                    .expect("ordinal of an inserted code")
+            })
+        })
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.combine(other, SetOp::Or)
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.combine(other, SetOp::And)
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        self.combine(other, SetOp::AndNot)
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.combine(other, SetOp::Xor)
+    }
+
+    /// Returns the approximate heap memory used, in bytes
+    pub fn heap_size(&self) -> usize {
+        self.containers.capacity() * std::mem::size_of::<(u16, Container)>()
+            + self
+                .containers
+                .iter()
+                .map(|(_, c)| match c {
+                    Container::Array(a) => a.capacity() * 2,
+                    Container::Bitmap(..) => 8192,
+                })
+                .sum::<usize>()
+    }
+
+    fn combine(&self, other: &Self, op: SetOp) -> Self {
+        let (a, b) = (&self.containers, &other.containers);
+        let mut containers = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            match (a.get(i), b.get(j)) {
+                (Some((ka, ca)), Some((kb, cb))) if ka == kb => {
+                    containers.extend(ca.combine(cb, op).map(|c| (*ka, c)));
+                    i += 1;
+                    j += 1;
+                }
+                (Some((ka, ca)), Some((kb, _))) if ka < kb => {
+                    if op.keeps(true, false) {
+                        containers.push((*ka, ca.clone()));
+                    }
+                    i += 1;
+                }
+                (Some((ka, ca)), None) => {
+                    if op.keeps(true, false) {
+                        containers.push((*ka, ca.clone()));
+                    }
+                    i += 1;
+                }
+                (_, Some((kb, cb))) => {
+                    if op.keeps(false, true) {
+                        containers.push((*kb, cb.clone()));
+                    }
+                    j += 1;
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+        Self {
+            containers,
+            _level: PhantomData,
+        }
+    }
+}
+
+#[inline]
+fn split_ordinal(ordinal: u32) -> (u16, u16) {
+    ((ordinal >> 16) as u16, ordinal as u16)
+}
+
+impl<L: MeshLevel> Default for MeshBitmap<L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<L: MeshLevel> std::fmt::Debug for MeshBitmap<L> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<L: MeshLevel> FromIterator<L> for MeshBitmap<L> {
+    fn from_iter<I: IntoIterator<Item = L>>(iter: I) -> Self {
+        let mut bitmap = Self::new();
+        bitmap.extend(iter);
+        bitmap
+    }
+}
+
+impl<L: MeshLevel> Extend<L> for MeshBitmap<L> {
+    fn extend<I: IntoIterator<Item = L>>(&mut self, iter: I) {
+        for code in iter {
+            self.insert(code);
+        }
+    }
+}
+
+macro_rules! impl_bitmap_op {
+    ($trait:ident, $method:ident, $op:expr) => {
+        impl<L: MeshLevel> std::ops::$trait for &MeshBitmap<L> {
+            type Output = MeshBitmap<L>;
+
+            fn $method(self, other: Self) -> MeshBitmap<L> {
+                self.combine(other, $op)
+            }
+        }
+    };
+}
+
+impl_bitmap_op!(BitOr, bitor, SetOp::Or);
+impl_bitmap_op!(BitAnd, bitand, SetOp::And);
+impl_bitmap_op!(Sub, sub, SetOp::AndNot);
+impl_bitmap_op!(BitXor, bitxor, SetOp::Xor);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gridsquare::random::SplitMix64;
+    use std::collections::BTreeSet;
+    use std::str::FromStr;
+
+    fn random_codes(seed: u64, n: usize, dense: bool) -> Vec<HalfCode> {
+        let mut rng = SplitMix64::new(seed);
+        let base = HalfCode::from_str("533946111").unwrap().ordinal();
+        let range = if dense { 20_000 } else { 10_000_000 };
+        (0..n)
+            .map(|_| HalfCode::from_ordinal(base + (rng.next_u64() % range) as u32).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut bitmap = MeshBitmap::<StandardCode>::new();
+        let code = StandardCode::from_str("53394611").unwrap();
+        assert!(bitmap.is_empty());
+        assert!(bitmap.insert(code));
+        assert!(!bitmap.insert(code));
+        assert!(bitmap.contains(code));
+        assert!(!bitmap.contains(StandardCode::from_str("53394612").unwrap()));
+        assert_eq!(bitmap.len(), 1);
+        assert!(bitmap.remove(code));
+        assert!(!bitmap.remove(code));
+        assert!(bitmap.is_empty());
+
+        // a whole secondary of eighths: array containers turn into bitmaps and back
+        let secondary = SecondaryCode::from_str("533946").unwrap();
+        let mut eighths = codes::<EighthCode>(std::iter::once(secondary.primary()), secondary)
+            .collect::<Vec<_>>();
+        let mut bitmap = eighths.iter().copied().collect::<MeshBitmap<_>>();
+        assert_eq!(bitmap.len(), 6400);
+        eighths.sort();
+        assert!(bitmap.iter().eq(eighths.iter().copied()));
+        for code in &eighths[100..] {
+            assert!(bitmap.remove(*code));
+        }
+        assert!(bitmap.iter().eq(eighths[..100].iter().copied()));
+    }
+
+    #[test]
+    fn test_set_operations() {
+        for (a_dense, b_dense) in [(false, false), (true, false), (false, true), (true, true)] {
+            let a = random_codes(1, 30_000, a_dense);
+            let b = random_codes(2, 30_000, b_dense);
+            let (ba, bb) = (
+                a.iter().copied().collect::<MeshBitmap<_>>(),
+                b.iter().copied().collect::<MeshBitmap<_>>(),
+            );
+            let (sa, sb) = (
+                a.into_iter().collect::<BTreeSet<_>>(),
+                b.into_iter().collect::<BTreeSet<_>>(),
+            );
+            assert_eq!(ba.len(), sa.len());
+            assert!(ba.iter().eq(sa.iter().copied()));
+            assert!((&ba | &bb).iter().eq(sa.union(&sb).copied()));
+            assert!((&ba & &bb).iter().eq(sa.intersection(&sb).copied()));
+            assert!((&ba - &bb).iter().eq(sa.difference(&sb).copied()));
+            assert!((&bb - &ba).iter().eq(sb.difference(&sa).copied()));
+            assert!((&ba ^ &bb).iter().eq(sa.symmetric_difference(&sb).copied()));
+            assert_eq!((&ba ^ &bb).len(), sa.symmetric_difference(&sb).count());
+            assert!((&ba - &ba).is_empty());
+            assert_eq!(ba.union(&ba), ba);
+        }
+    }
+
+    #[test]
+    fn test_land_size() {
+        // every standard code in the land primaries
+        let bitmap = codes::<StandardCode>(primaries_in_land(), None).collect::<MeshBitmap<_>>();
+        assert_eq!(bitmap.len(), primaries_in_land().count() * 6400);
+        assert!(bitmap.heap_size() < 2_000_000, "{}", bitmap.heap_size());
+    }
+}
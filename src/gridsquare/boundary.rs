@@ -0,0 +1,203 @@
+// Boundary filters for patch iteration
+
+use super::*;
+
+/// How much of a patch is covered by a boundary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Coverage {
+    /// The patch intersects the boundary but is not fully contained
+    Partial,
+    /// The patch is fully contained in the boundary
+    Full,
+}
+
+/// A region used to filter (and prune) patch iteration
+pub trait Boundary {
+    /// Returns true if the patch intersects the region
+    fn intersects(&self, patch: &LngLatBox) -> bool;
+
+    /// Returns true if the patch is fully contained in the region
+    ///
+    /// Answering false for a contained patch is allowed; it only disables pruning.
+    fn contains(&self, patch: &LngLatBox) -> bool;
+
+    /// Classifies the patch, or returns None if it does not intersect the region
+    fn coverage(&self, patch: &LngLatBox) -> Option<Coverage> {
+        if !self.intersects(patch) {
+            None
+        } else if self.contains(patch) {
+            Some(Coverage::Full)
+        } else {
+            Some(Coverage::Partial)
+        }
+    }
+}
+
+impl Boundary for LngLatBox {
+    #[inline]
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        self.intersects_box(patch)
+    }
+
+    #[inline]
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        self.contains_box(patch)
+    }
+}
+
+/// `None` means no boundary, i.e. everything is contained
+impl Boundary for Option<LngLatBox> {
+    #[inline]
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        self.is_none_or(|b| b.intersects_box(patch))
+    }
+
+    #[inline]
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        self.is_none_or(|b| b.contains_box(patch))
+    }
+}
+
+impl<B: Boundary + ?Sized> Boundary for &B {
+    #[inline]
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        (**self).intersects(patch)
+    }
+
+    #[inline]
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        (**self).contains(patch)
+    }
+
+    #[inline]
+    fn coverage(&self, patch: &LngLatBox) -> Option<Coverage> {
+        (**self).coverage(patch)
+    }
+}
+
+/// Union of regions
+///
+/// A patch is reported as contained only when a single member contains it.
+impl<B: Boundary> Boundary for [B] {
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        self.iter().any(|b| b.intersects(patch))
+    }
+
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        self.iter().any(|b| b.contains(patch))
+    }
+}
+
+impl<B: Boundary> Boundary for Vec<B> {
+    #[inline]
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        Boundary::intersects(self.as_slice(), patch)
+    }
+
+    #[inline]
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        Boundary::contains(self.as_slice(), patch)
+    }
+}
+
+/// Returns true if the two boxes share a region of positive area
+#[inline]
+pub(crate) fn overlaps(a: &LngLatBox, b: &LngLatBox) -> bool {
+    a.min.vlng < b.max.vlng
+        && a.max.vlng > b.min.vlng
+        && a.min.vlat < b.max.vlat
+        && a.max.vlat > b.min.vlat
+}
+
+macro_rules! impl_code_boundary {
+    ($($t:ty),*) => {
+        $(
+            /// Codes as regions; patches merely touching the edge of the code do not intersect it
+            impl Boundary for $t {
+                #[inline]
+                fn intersects(&self, patch: &LngLatBox) -> bool {
+                    overlaps(&self.envelope(), patch)
+                }
+
+                #[inline]
+                fn contains(&self, patch: &LngLatBox) -> bool {
+                    self.envelope().contains_box(patch)
+                }
+            }
+        )*
+    };
+}
+
+impl_code_boundary!(PrimaryCode, SecondaryCode, StandardCode, LevelAndCode);
+
+/// Codes as regions; patches merely touching the edge of the code do not intersect it
+impl<P: GridSquareCode> Boundary for Quad<P> {
+    #[inline]
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        overlaps(&self.envelope(), patch)
+    }
+
+    #[inline]
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        self.envelope().contains_box(patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_box_union() {
+        let a = LngLatBox::new(LngLat::new(141.30, 42.93), LngLat::new(141.40, 43.00));
+        let b = LngLatBox::new(LngLat::new(141.45, 42.93), LngLat::new(141.56, 43.12));
+        let boxes = [a, b];
+
+        let union_count = codes::<HalfCode>(primaries_in_land(), &boxes[..]).count();
+        let count_a = codes::<HalfCode>(primaries_in_land(), a).count();
+        let count_b = codes::<HalfCode>(primaries_in_land(), b).count();
+        assert_eq!(union_count, count_a + count_b);
+        assert_eq!(
+            codes::<HalfCode>(primaries_in_land(), &boxes.to_vec()).count(),
+            union_count
+        );
+    }
+
+    #[test]
+    fn test_code_set() {
+        let standards = [
+            StandardCode::from_str("64414278").unwrap(),
+            StandardCode::from_str("64414279").unwrap(),
+            StandardCode::from_str("53394611").unwrap(),
+        ];
+        let mut quarters = codes::<QuarterCode>(primaries_in_land(), &standards[..])
+            .map(|c| c.standard())
+            .collect::<Vec<_>>();
+        assert_eq!(quarters.len(), 3 * 16);
+        quarters.sort();
+        quarters.dedup();
+        let mut expected = standards.to_vec();
+        expected.sort();
+        assert_eq!(quarters, expected);
+    }
+
+    #[test]
+    fn test_pruning() {
+        let secondary = SecondaryCode::from_str("644142").unwrap();
+        let patches = patches::<EighthCode>(primaries_in_land(), secondary).collect::<Vec<_>>();
+        assert_eq!(patches.len(), 100 * 64);
+
+        let classified =
+            EighthCode::iter_within(secondary.primary(), secondary).collect::<Vec<_>>();
+        assert!(classified.iter().all(|(_, _, c)| *c == Coverage::Full));
+
+        let boundary = LngLatBox::new(LngLat::new(141.0, 43.0), LngLat::new(141.31, 43.1));
+        let classified = StandardCode::iter_within(secondary.primary(), boundary);
+        let (full, partial): (Vec<_>, Vec<_>) =
+            classified.partition(|(_, _, c)| *c == Coverage::Full);
+        assert!(!full.is_empty() && !partial.is_empty());
+        assert!(full.iter().all(|(_, e, _)| boundary.contains_box(e)));
+        assert!(partial.iter().all(|(_, e, _)| !boundary.contains_box(e)));
+    }
+}
@@ -0,0 +1,182 @@
+// Radius coverage (cells within a geodesic distance of a point)
+
+use super::ellipsoid::{F, distance, meridian_radius, prime_vertical_radius};
+use super::*;
+
+/// A geodesic circle on the GRS80 ellipsoid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Circle {
+    center: LngLat,
+    radius_m: f64,
+}
+
+impl Circle {
+    pub fn new(center: LngLat, radius_m: f64) -> Self {
+        Self { center, radius_m }
+    }
+
+    pub fn center(&self) -> LngLat {
+        self.center
+    }
+
+    pub fn radius_m(&self) -> f64 {
+        self.radius_m
+    }
+
+    /// Returns a box enclosing the circle
+    pub fn bounds(&self) -> LngLatBox {
+        // widen by 1% to stay on the safe side of the local approximation
+        let r = self.radius_m * 1.01;
+        let lat = self.center.lat();
+        let dlat = (r / meridian_radius(lat.to_radians())).to_degrees();
+        let poleward = (lat.abs() + dlat).min(89.9).to_radians();
+        let dlng = (r / (prime_vertical_radius(poleward) * poleward.cos())).to_degrees();
+        LngLatBox::new(
+            LngLat::new(self.center.lng() - dlng, lat - dlat),
+            LngLat::new(self.center.lng() + dlng, lat + dlat),
+        )
+    }
+
+    /// Geodesic distance in meters from the center to the nearest point of the box
+    ///
+    /// Along a parallel the nearest point is at the longitude of the center. Along a meridian it
+    /// is poleward of the center, where the geodesic meets the meridian at a right angle.
+    fn min_distance(&self, patch: &LngLatBox) -> f64 {
+        let (min, max) = (patch.min(), patch.max());
+        let (lng0, lat0) = (self.center.lng(), self.center.lat());
+        let lng = lng0.clamp(min.lng(), max.lng());
+        let lat = if lng == lng0 {
+            lat0
+        } else {
+            // foot of the perpendicular on the auxiliary sphere, in reduced latitudes
+            let u0 = ((1.0 - F) * lat0.to_radians().tan()).atan();
+            let u = u0.tan().atan2((lng - lng0).to_radians().cos());
+            (u.tan() / (1.0 - F)).atan().to_degrees()
+        };
+        distance(lng0, lat0, lng, lat.clamp(min.lat(), max.lat()))
+    }
+
+    /// Geodesic distance in meters from the center to the farthest corner of the box
+    fn max_distance(&self, patch: &LngLatBox) -> f64 {
+        let (min, max) = (patch.min(), patch.max());
+        [
+            (min.lng(), min.lat()),
+            (max.lng(), min.lat()),
+            (min.lng(), max.lat()),
+            (max.lng(), max.lat()),
+        ]
+        .into_iter()
+        .map(|(lng, lat)| distance(self.center.lng(), self.center.lat(), lng, lat))
+        .fold(0.0, f64::max)
+    }
+}
+
+impl Boundary for Circle {
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        self.min_distance(patch) <= self.radius_m
+    }
+
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        self.max_distance(patch) <= self.radius_m
+    }
+}
+
+/// Returns the codes of level `L` whose envelope intersects the geodesic circle, classified as
+/// fully within the circle or partially intersecting it
+///
+/// The nearest point of a cell lies on its edge at the foot of the perpendicular geodesic,
+/// located on the auxiliary sphere, which is accurate to well below a millimeter within Japan.
+pub fn radius_coverage<L: MeshLevel>(
+    center: LngLat,
+    radius_m: f64,
+) -> impl Iterator<Item = (L, Coverage)> {
+    let circle = Circle::new(center, radius_m);
+    primaries_from_bounds(circle.bounds()).flat_map(move |prim| {
+        L::iter_within(prim, circle).map(|(code, _, coverage)| (code, coverage))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_radius_coverage() {
+        // 250m meshes within 2 km of Tokyo Station
+        let center = LngLat::new(139.767125, 35.681236);
+        let cells = radius_coverage::<QuarterCode>(center, 2_000.0).collect::<Vec<_>>();
+        let full = cells.iter().filter(|(_, c)| *c == Coverage::Full).count();
+        // a quarter code at 35.7°N is about 282m x 231m; the circle is about 12.6 km²
+        let cell_area = 282.0 * 231.0;
+        let circle_area = std::f64::consts::PI * 2_000.0 * 2_000.0;
+        assert!((full as f64) < circle_area / cell_area);
+        assert!((cells.len() as f64) > circle_area / cell_area);
+        assert!(
+            cells
+                .iter()
+                .any(|(c, _)| *c == QuarterCode::from_lnglat(center).unwrap())
+        );
+
+        let circle = Circle::new(center, 2_000.0);
+        for (code, coverage) in &cells {
+            let e = code.envelope();
+            assert!(circle.min_distance(&e) <= 2_000.0);
+            assert_eq!(
+                *coverage == Coverage::Full,
+                circle.max_distance(&e) <= 2_000.0
+            );
+        }
+
+        // brute force over the bounding box gives the same set
+        let expected = codes_in_box::<QuarterCode>(circle.bounds())
+            .filter(|c| circle.intersects(&c.envelope()))
+            .count();
+        assert_eq!(cells.len(), expected);
+
+        // fewer cells across east-west than north-south, since cells are wider than tall in meters
+        let xs = cells.iter().map(|(c, _)| c.index_xy().0);
+        let ys = cells.iter().map(|(c, _)| c.index_xy().1);
+        let width = xs.clone().max().unwrap() - xs.min().unwrap();
+        let height = ys.clone().max().unwrap() - ys.min().unwrap();
+        assert!(width < height);
+    }
+
+    #[test]
+    fn test_high_latitude() {
+        // north of Hokkaido, where meridians converge the most within the domain
+        let center = LngLat::new(141.0, 45.4);
+        let circle = Circle::new(center, 100_000.0);
+
+        // the nearest point of an edge east of the center is poleward of it, by about 1 km here
+        let patch = LngLatBox::new(LngLat::new(142.0, 45.4), LngLat::new(142.5, 45.6));
+        let along_edge = (0..=20_000)
+            .map(|i| distance(141.0, 45.4, 142.0, 45.4 + 0.2 * i as f64 / 20_000.0))
+            .fold(f64::INFINITY, f64::min);
+        let nearest = circle.min_distance(&patch);
+        assert!((nearest - along_edge).abs() < 1e-3);
+        assert!(nearest < distance(141.0, 45.4, 142.0, 45.4) - 1.0);
+        assert!(circle.intersects(&patch));
+
+        // every point within 10 km falls in a code of the coverage
+        let radius = 10_000.0;
+        let circle = Circle::new(center, radius);
+        let cells = radius_coverage::<StandardCode>(center, radius)
+            .map(|(code, _)| code)
+            .collect::<std::collections::HashSet<_>>();
+        let (min, max) = (circle.bounds().min(), circle.bounds().max());
+        for i in 0..=200 {
+            for j in 0..=200 {
+                let lng = min.lng() + (max.lng() - min.lng()) * i as f64 / 200.0;
+                let lat = min.lat() + (max.lat() - min.lat()) * j as f64 / 200.0;
+                if distance(center.lng(), center.lat(), lng, lat) <= radius {
+                    let code = StandardCode::from_lnglat(LngLat::new(lng, lat)).unwrap();
+                    assert!(cells.contains(&code));
+                }
+            }
+        }
+        let expected = codes_in_box::<StandardCode>(circle.bounds())
+            .filter(|c| circle.intersects(&c.envelope()))
+            .count();
+        assert_eq!(cells.len(), expected);
+    }
+}
@@ -14,16 +14,19 @@
 // 3 4
 // 1 2
 
+use super::ellipsoid;
+use super::level::{Level, MeshLevel};
 use super::lnglat::{LngLat, LngLatBox};
+use super::random::SplitMix64;
 use crate::Error;
-use std::{fmt::Display, str::FromStr};
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LevelAndCode {
     Primary(PrimaryCode),
     Secondary(SecondaryCode),
-    // X5,
-    // X2,
+    X5(X5Code),
+    X2(X2Code),
     Standard(StandardCode),
     Half(HalfCode),
     Quarter(QuarterCode),
@@ -37,9 +40,11 @@ impl LevelAndCode {
         Ok(match digits {
             4 => LevelAndCode::Primary(PrimaryCode::from_int(code as u16)?),
             6 => LevelAndCode::Secondary(SecondaryCode::from_int(code as u32)?),
-            7 => todo!(), // X5
+            7 => LevelAndCode::X5(X5Code::from_int(code as u32)?),
             8 => LevelAndCode::Standard(StandardCode::from_int(code as u32)?),
-            9 => LevelAndCode::Half(HalfCode::from_int(code as u32)?), // TODO: or X2
+            // half codes end with 1-4, x2 codes with 5
+            9 if code % 10 == 5 => LevelAndCode::X2(X2Code::from_int(code as u32)?),
+            9 => LevelAndCode::Half(HalfCode::from_int(code as u32)?),
             10 => LevelAndCode::Quarter(QuarterCode::from_int(code)?),
             11 => LevelAndCode::Eighth(EighthCode::from_int(code)?),
             _ => return Err(Error::InvalidCode),
@@ -47,23 +52,206 @@ impl LevelAndCode {
     }
 }
 
+impl LevelAndCode {
+    /// Automatically determine the level and code from ASCII digits
+    pub fn from_ascii(code: &[u8]) -> Result<LevelAndCode, Error> {
+        Ok(match code.len() {
+            4 => LevelAndCode::Primary(PrimaryCode::from_ascii(code)?),
+            6 => LevelAndCode::Secondary(SecondaryCode::from_ascii(code)?),
+            7 => LevelAndCode::X5(X5Code::from_ascii(code)?),
+            8 => LevelAndCode::Standard(StandardCode::from_ascii(code)?),
+            9 if code[8] == b'5' => LevelAndCode::X2(X2Code::from_ascii(code)?),
+            9 => LevelAndCode::Half(HalfCode::from_ascii(code)?),
+            10 => LevelAndCode::Quarter(QuarterCode::from_ascii(code)?),
+            11 => LevelAndCode::Eighth(EighthCode::from_ascii(code)?),
+            _ => return Err(Error::InvalidCode),
+        })
+    }
+}
+
+macro_rules! dispatch {
+    ($value:expr, $code:ident => $body:expr) => {
+        match $value {
+            LevelAndCode::Primary($code) => $body,
+            LevelAndCode::Secondary($code) => $body,
+            LevelAndCode::X5($code) => $body,
+            LevelAndCode::X2($code) => $body,
+            LevelAndCode::Standard($code) => $body,
+            LevelAndCode::Half($code) => $body,
+            LevelAndCode::Quarter($code) => $body,
+            LevelAndCode::Eighth($code) => $body,
+        }
+    };
+}
+
+impl LevelAndCode {
+    pub fn level(&self) -> Level {
+        match self {
+            LevelAndCode::Primary(_) => Level::Primary,
+            LevelAndCode::Secondary(_) => Level::Secondary,
+            LevelAndCode::X5(_) => Level::X5,
+            LevelAndCode::X2(_) => Level::X2,
+            LevelAndCode::Standard(_) => Level::Standard,
+            LevelAndCode::Half(_) => Level::Half,
+            LevelAndCode::Quarter(_) => Level::Quarter,
+            LevelAndCode::Eighth(_) => Level::Eighth,
+        }
+    }
+
+    /// Returns the code as an integer
+    pub fn to_int(&self) -> u64 {
+        dispatch!(self, c => c.to_int())
+    }
+
+    /// Returns the code of the given level at (x, y) (see [`GridSquareCode::index_xy`])
+    pub fn from_index_xy(level: Level, x: u32, y: u32) -> Result<Self, Error> {
+        Ok(match level {
+            Level::Primary => PrimaryCode::from_index_xy(x, y)?.into(),
+            Level::Secondary => SecondaryCode::from_index_xy(x, y)?.into(),
+            Level::X5 => X5Code::from_index_xy(x, y)?.into(),
+            Level::X2 => X2Code::from_index_xy(x, y)?.into(),
+            Level::Standard => StandardCode::from_index_xy(x, y)?.into(),
+            Level::Half => HalfCode::from_index_xy(x, y)?.into(),
+            Level::Quarter => QuarterCode::from_index_xy(x, y)?.into(),
+            Level::Eighth => EighthCode::from_index_xy(x, y)?.into(),
+        })
+    }
+
+    /// Returns the code of the given level containing the point
+    pub fn from_lnglat(level: Level, lnglat: LngLat) -> Result<Self, Error> {
+        Ok(match level {
+            Level::Primary => PrimaryCode::from_lnglat(lnglat)?.into(),
+            Level::Secondary => SecondaryCode::from_lnglat(lnglat)?.into(),
+            Level::X5 => X5Code::from_lnglat(lnglat)?.into(),
+            Level::X2 => X2Code::from_lnglat(lnglat)?.into(),
+            Level::Standard => StandardCode::from_lnglat(lnglat)?.into(),
+            Level::Half => HalfCode::from_lnglat(lnglat)?.into(),
+            Level::Quarter => QuarterCode::from_lnglat(lnglat)?.into(),
+            Level::Eighth => EighthCode::from_lnglat(lnglat)?.into(),
+        })
+    }
+
+    /// Returns the ancestor (or the code itself) at the given level, or None if the level is finer
+    ///
+    /// Integrated codes are the ancestors of the standard and finer codes inside them, and
+    /// x5 and x2 codes are not nested in each other.
+    pub fn ancestor(&self, level: Level) -> Option<Self> {
+        let own = self.level();
+        if level > own || !own.divisions().is_multiple_of(level.divisions()) {
+            return None;
+        }
+        let ratio = own.divisions() / level.divisions();
+        let (x, y) = self.index_xy();
+        Self::from_index_xy(level, x / ratio, y / ratio).ok()
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        self.ancestor(self.level().parent()?)
+    }
+
+    /// Returns an iterator over the children in code order
+    ///
+    /// Secondary codes have standard children; integrated codes have none.
+    pub fn children(&self) -> impl Iterator<Item = Self> {
+        let child = self.level().child();
+        let split = child.map_or(0, |c| c.split());
+        let (x, y) = self.index_xy();
+        (0..split).flat_map(move |dy| {
+            (0..split).map(move |dx| {
+                Self::from_index_xy(child.unwrap(), x * split + dx, y * split + dy)
+                    .expect("children are within the domain")
+            })
+        })
+    }
+
+    /// Returns true if `other` is this code or one of its descendants
+    pub fn contains(&self, other: &Self) -> bool {
+        other.ancestor(self.level()).as_ref() == Some(self)
+    }
+}
+
+impl GridSquareCode for LevelAndCode {
+    fn envelope(&self) -> LngLatBox {
+        dispatch!(self, c => c.envelope())
+    }
+
+    fn index_xy(&self) -> (u32, u32) {
+        dispatch!(self, c => c.index_xy())
+    }
+}
+
+impl Display for LevelAndCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        dispatch!(self, c => c.fmt(f))
+    }
+}
+
+/// Codes are ordered as their string representations, so descendants directly follow their ancestors
+/// in the quad chain
+impl Ord for LevelAndCode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let key = |c: &Self| {
+            let level = c.level();
+            (c.to_int() * 10u64.pow(11 - level.digits()), level)
+        };
+        key(self).cmp(&key(other))
+    }
+}
+
+impl PartialOrd for LevelAndCode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+macro_rules! impl_from_code {
+    ($($variant:ident($t:ty)),*) => {
+        $(
+            impl From<$t> for LevelAndCode {
+                #[inline]
+                fn from(code: $t) -> Self {
+                    LevelAndCode::$variant(code)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_code!(
+    Primary(PrimaryCode),
+    Secondary(SecondaryCode),
+    X5(X5Code),
+    X2(X2Code),
+    Standard(StandardCode),
+    Half(HalfCode),
+    Quarter(QuarterCode),
+    Eighth(EighthCode)
+);
+
 impl FromStr for LevelAndCode {
     type Err = Error;
 
     /// Automatically determine the level and code from a string value
     fn from_str(code: &str) -> Result<LevelAndCode, Error> {
-        let digits = code.len();
-        Ok(match digits {
-            4 => LevelAndCode::Primary(PrimaryCode::from_str(code)?),
-            6 => LevelAndCode::Secondary(SecondaryCode::from_str(code)?),
-            7 => todo!(), // X5
-            8 => LevelAndCode::Standard(StandardCode::from_str(code)?),
-            9 => LevelAndCode::Half(HalfCode::from_str(code)?), // TODO: or X2
-            10 => LevelAndCode::Quarter(QuarterCode::from_str(code)?),
-            11 => LevelAndCode::Eighth(EighthCode::from_str(code)?),
-            _ => return Err(Error::InvalidCode),
-        })
+        LevelAndCode::from_ascii(code.as_bytes())
+    }
+}
+
+/// Parses a single ASCII digit, rejecting values greater than `max`
+#[inline(always)]
+fn ascii_digit(b: u8, max: u8) -> Result<u8, Error> {
+    let d = b.wrapping_sub(b'0');
+    if d > max {
+        return Err(Error::InvalidCode);
     }
+    Ok(d)
+}
+
+/// Maps a fraction in [0, 1) to a sampling position away from the edges of a cell
+#[inline]
+fn inset(t: f64) -> f64 {
+    const MARGIN: f64 = 1e-9;
+    MARGIN + t * (1.0 - 2.0 * MARGIN)
 }
 
 pub trait GridSquareCode {
@@ -71,9 +259,107 @@ pub trait GridSquareCode {
     fn envelope(&self) -> LngLatBox;
 
     fn index_xy(&self) -> (u32, u32);
+
+    /// Returns the center point of the code
+    fn center(&self) -> LngLat {
+        self.envelope().center()
+    }
+
+    /// Returns the four corners counter-clockwise, starting from the south-west corner
+    fn corners(&self) -> [LngLat; 4] {
+        self.envelope().corners()
+    }
+
+    /// Returns the outline as a closed counter-clockwise ring with each edge divided into `segments` parts
+    ///
+    /// Intermediate vertices keep edges accurate after reprojection, where they no longer are straight lines.
+    fn ring(&self, segments: usize) -> Vec<LngLat> {
+        let corners = self.corners();
+        let n = segments.max(1);
+        let mut ring = Vec::with_capacity(4 * n + 1);
+        for (i, a) in corners.iter().enumerate() {
+            let b = corners[(i + 1) % 4];
+            ring.extend((0..n).map(|k| {
+                let t = k as f64 / n as f64;
+                LngLat::new_raw(
+                    a.vlng + (b.vlng - a.vlng) * t,
+                    a.vlat + (b.vlat - a.vlat) * t,
+                )
+            }));
+        }
+        ring.push(corners[0]);
+        ring
+    }
+
+    /// Returns the fractional position (u, v) of a point relative to the envelope
+    ///
+    /// Points inside the code map to [0, 1)², with u growing eastward and v northward.
+    fn to_local(&self, lnglat: LngLat) -> (f64, f64) {
+        let LngLatBox { min, max } = self.envelope();
+        (
+            (lnglat.vlng - min.vlng) / (max.vlng - min.vlng),
+            (lnglat.vlat - min.vlat) / (max.vlat - min.vlat),
+        )
+    }
+
+    /// Returns the point at the fractional position (u, v) relative to the envelope
+    fn at_local(&self, u: f64, v: f64) -> LngLat {
+        let LngLatBox { min, max } = self.envelope();
+        LngLat::new_raw(
+            min.vlng + (max.vlng - min.vlng) * u,
+            min.vlat + (max.vlat - min.vlat) * v,
+        )
+    }
+
+    /// Returns an endless sequence of pseudo-random points uniformly distributed in the code
+    ///
+    /// The sequence is determined by the seed and the size and position of the code, so codes
+    /// sampled with the same seed still get different points. Points keep a margin of a billionth
+    /// of the cell from its edges, so rounding never moves them to a neighboring code.
+    fn sample_points(&self, seed: u64) -> impl Iterator<Item = LngLat> {
+        let (x, y) = self.index_xy();
+        let LngLatBox { min, max } = self.envelope();
+        let mut rng = SplitMix64::new(seed ^ (max.vlng - min.vlng).to_bits());
+        rng = SplitMix64::new(rng.next_u64() ^ ((x as u64) << 32 | y as u64));
+        std::iter::repeat_with(move || {
+            let (u, v) = (inset(rng.next_f64()), inset(rng.next_f64()));
+            LngLat::new_raw(
+                min.vlng + (max.vlng - min.vlng) * u,
+                min.vlat + (max.vlat - min.vlat) * v,
+            )
+        })
+    }
+
+    /// Returns the area in m² on the GRS80 ellipsoid
+    fn area_m2(&self) -> f64 {
+        let e = self.envelope();
+        ellipsoid::box_area(e.min.lng(), e.min.lat(), e.max.lng(), e.max.lat())
+    }
+
+    /// Returns the east-west width in meters, measured along the parallel through the center
+    fn width_m(&self) -> f64 {
+        let e = self.envelope();
+        let phi = ((e.min.lat() + e.max.lat()) / 2.0).to_radians();
+        ellipsoid::parallel_arc(phi, (e.max.lng() - e.min.lng()).to_radians())
+    }
+
+    /// Returns the north-south height in meters, measured along a meridian
+    fn height_m(&self) -> f64 {
+        let e = self.envelope();
+        ellipsoid::meridian_arc(e.min.lat().to_radians(), e.max.lat().to_radians())
+    }
+
+    /// Returns the geodesic distance in meters between the centers of two codes
+    fn distance_m(&self, other: &impl GridSquareCode) -> f64
+    where
+        Self: Sized,
+    {
+        let (a, b) = (self.center(), other.center());
+        ellipsoid::distance(a.lng(), a.lat(), b.lng(), b.lat())
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PrimaryCode {
     /// "YY--"
     pub(crate) y: u8,
@@ -114,6 +400,18 @@ impl PrimaryCode {
         Ok(Self { y, x })
     }
 
+    /// Parses a code from exactly 4 ASCII digits
+    #[inline]
+    pub fn from_ascii(s: &[u8]) -> Result<Self, Error> {
+        let [y0, y1, x0, x1] = *s else {
+            return Err(Error::InvalidCode);
+        };
+        Ok(Self {
+            y: ascii_digit(y0, 9)? * 10 + ascii_digit(y1, 9)?,
+            x: ascii_digit(x0, 9)? * 10 + ascii_digit(x1, 9)?,
+        })
+    }
+
     #[inline]
     pub fn y1(&self) -> u8 {
         self.y
@@ -124,10 +422,10 @@ impl PrimaryCode {
         self.x
     }
 
-    /// Returns an iterator over child secondary codes
+    /// Returns an iterator over child secondary codes in code order
     pub fn iter_secondary(self) -> impl Iterator<Item = SecondaryCode> {
-        (0..=7).flat_map(move |x2| {
-            (0..=7).map(move |y2| SecondaryCode {
+        (0..=7).flat_map(move |y2| {
+            (0..=7).map(move |x2| SecondaryCode {
                 primary: self,
                 y2,
                 x2,
@@ -160,16 +458,7 @@ impl FromStr for PrimaryCode {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 4 {
-            return Err(Error::InvalidCode);
-        }
-        if let Some((y1_str, x1_str)) = s.split_at_checked(2) {
-            let y1 = y1_str.parse::<u8>().map_err(|_| Error::InvalidCode)?;
-            let x1 = x1_str.parse::<u8>().map_err(|_| Error::InvalidCode)?;
-            Ok(Self { y: y1, x: x1 })
-        } else {
-            Err(Error::InvalidCode)
-        }
+        Self::from_ascii(s.as_bytes())
     }
 }
 
@@ -179,13 +468,13 @@ impl Display for PrimaryCode {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SecondaryCode {
-    primary: PrimaryCode,
+    pub(crate) primary: PrimaryCode,
     /// "----Y-"
-    y2: u8,
+    pub(crate) y2: u8,
     /// "-----X"
-    x2: u8,
+    pub(crate) x2: u8,
 }
 
 impl SecondaryCode {
@@ -217,6 +506,19 @@ impl SecondaryCode {
         })
     }
 
+    /// Parses a code from exactly 6 ASCII digits
+    #[inline]
+    pub fn from_ascii(s: &[u8]) -> Result<Self, Error> {
+        let [primary @ .., y2, x2] = s else {
+            return Err(Error::InvalidCode);
+        };
+        Ok(Self {
+            primary: PrimaryCode::from_ascii(primary)?,
+            y2: ascii_digit(*y2, 7)?,
+            x2: ascii_digit(*x2, 7)?,
+        })
+    }
+
     #[inline]
     pub fn y1(&self) -> u8 {
         self.primary.y
@@ -242,9 +544,10 @@ impl SecondaryCode {
         self.primary
     }
 
+    /// Returns an iterator over child standard codes in code order
     pub fn iter_standard(self) -> impl Iterator<Item = StandardCode> {
-        (0..=9).flat_map(move |x3| {
-            (0..=9).map(move |y3| StandardCode {
+        (0..=9).flat_map(move |y3| {
+            (0..=9).map(move |x3| StandardCode {
                 secondary: self,
                 y3,
                 x3,
@@ -278,45 +581,254 @@ impl FromStr for SecondaryCode {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 6 {
+        Self::from_ascii(s.as_bytes())
+    }
+}
+
+impl Display for SecondaryCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}{:02}{}{}",
+            self.primary.y, self.primary.x, self.y2, self.x2
+        )
+    }
+}
+
+/// 5-times mesh code: a secondary code divided into 2×2 (about 5km)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct X5Code {
+    pub(crate) secondary: SecondaryCode,
+    /// 1-4
+    pub(crate) quad: u8,
+}
+
+impl X5Code {
+    #[inline]
+    pub fn from_int(code: u32) -> Result<Self, Error> {
+        if code > 9999999 {
+            return Err(Error::InvalidCode);
+        }
+        let quad = (code % 10) as u8;
+        if !(1..=4).contains(&quad) {
             return Err(Error::InvalidCode);
         }
-        let Some((primary, rest)) = s.split_at_checked(4) else {
+        Ok(Self {
+            secondary: SecondaryCode::from_int(code / 10)?,
+            quad,
+        })
+    }
+
+    #[inline]
+    pub fn from_lnglat(lnglat: LngLat) -> Result<Self, Error> {
+        let yd = ((lnglat.vlat / 20. * 8. * 2.) as u64 % 2) as u8;
+        let xd = ((lnglat.vlng / 30. * 8. * 2.) as u64 % 2) as u8;
+        Ok(Self {
+            secondary: SecondaryCode::from_lnglat(lnglat)?,
+            quad: (yd << 1) + xd + 1,
+        })
+    }
+
+    /// Parses a code from exactly 7 ASCII digits
+    #[inline]
+    pub fn from_ascii(s: &[u8]) -> Result<Self, Error> {
+        let [secondary @ .., quad] = s else {
             return Err(Error::InvalidCode);
         };
-        let Some((y2_str, x2_str)) = rest.split_at_checked(1) else {
+        let quad = ascii_digit(*quad, 4)?;
+        if quad == 0 {
+            return Err(Error::InvalidCode);
+        }
+        Ok(Self {
+            secondary: SecondaryCode::from_ascii(secondary)?,
+            quad,
+        })
+    }
+
+    #[inline]
+    pub fn quad(&self) -> u8 {
+        self.quad
+    }
+
+    #[inline]
+    pub fn primary(&self) -> PrimaryCode {
+        self.secondary.primary
+    }
+
+    #[inline]
+    pub fn secondary(&self) -> SecondaryCode {
+        self.secondary
+    }
+
+    /// Returns the 5×5 standard codes making up this code, in code order
+    pub fn iter_standard(self) -> impl Iterator<Item = StandardCode> {
+        let (dx, dy) = ((self.quad - 1) & 1, (self.quad - 1) >> 1);
+        (0..5).flat_map(move |y| {
+            (0..5).map(move |x| StandardCode {
+                secondary: self.secondary,
+                y3: dy * 5 + y,
+                x3: dx * 5 + x,
+            })
+        })
+    }
+}
+
+impl GridSquareCode for X5Code {
+    fn envelope(&self) -> LngLatBox {
+        let d = self.quad - 1;
+        self.secondary.envelope().split::<2>(d & 1, d >> 1)
+    }
+
+    #[inline]
+    fn index_xy(&self) -> (u32, u32) {
+        let (px, py) = self.secondary.index_xy();
+        let d = (self.quad - 1) as u32;
+        (px * 2 + (d & 1), py * 2 + (d >> 1))
+    }
+}
+
+impl FromStr for X5Code {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_ascii(s.as_bytes())
+    }
+}
+
+impl Display for X5Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.secondary, self.quad)
+    }
+}
+
+/// 2-times mesh code: a secondary code divided into 5×5 (about 2km)
+///
+/// It is written as the secondary code followed by the even row and column digits of its
+/// south-west standard code and a trailing `5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct X2Code {
+    pub(crate) secondary: SecondaryCode,
+    /// 0-4
+    pub(crate) y: u8,
+    /// 0-4
+    pub(crate) x: u8,
+}
+
+impl X2Code {
+    #[inline]
+    pub fn from_int(code: u32) -> Result<Self, Error> {
+        if code > 999999999 || code % 10 != 5 {
+            return Err(Error::InvalidCode);
+        }
+        let y = ((code / 100) % 10) as u8;
+        let x = ((code / 10) % 10) as u8;
+        if !y.is_multiple_of(2) || !x.is_multiple_of(2) {
+            return Err(Error::InvalidCode);
+        }
+        Ok(Self {
+            secondary: SecondaryCode::from_int(code / 1000)?,
+            y: y / 2,
+            x: x / 2,
+        })
+    }
+
+    #[inline]
+    pub fn from_lnglat(lnglat: LngLat) -> Result<Self, Error> {
+        let yd = (lnglat.vlat / 20. * 8. * 5.) as u64 % 5;
+        let xd = (lnglat.vlng / 30. * 8. * 5.) as u64 % 5;
+        Ok(Self {
+            secondary: SecondaryCode::from_lnglat(lnglat)?,
+            y: yd as u8,
+            x: xd as u8,
+        })
+    }
+
+    /// Parses a code from exactly 9 ASCII digits
+    #[inline]
+    pub fn from_ascii(s: &[u8]) -> Result<Self, Error> {
+        let [secondary @ .., y, x, b'5'] = s else {
             return Err(Error::InvalidCode);
         };
-        let y2 = y2_str.parse::<u8>().map_err(|_| Error::InvalidCode)?;
-        let x2 = x2_str.parse::<u8>().map_err(|_| Error::InvalidCode)?;
-        if y2 > 7 || x2 > 7 {
+        let (y, x) = (ascii_digit(*y, 8)?, ascii_digit(*x, 8)?);
+        if !y.is_multiple_of(2) || !x.is_multiple_of(2) {
             return Err(Error::InvalidCode);
         }
         Ok(Self {
-            primary: PrimaryCode::from_str(primary)?,
-            y2,
-            x2,
+            secondary: SecondaryCode::from_ascii(secondary)?,
+            y: y / 2,
+            x: x / 2,
+        })
+    }
+
+    /// Returns the row digit as written in the code (0, 2, 4, 6 or 8)
+    #[inline]
+    pub fn y3(&self) -> u8 {
+        self.y * 2
+    }
+
+    /// Returns the column digit as written in the code (0, 2, 4, 6 or 8)
+    #[inline]
+    pub fn x3(&self) -> u8 {
+        self.x * 2
+    }
+
+    #[inline]
+    pub fn primary(&self) -> PrimaryCode {
+        self.secondary.primary
+    }
+
+    #[inline]
+    pub fn secondary(&self) -> SecondaryCode {
+        self.secondary
+    }
+
+    /// Returns the 2×2 standard codes making up this code, in code order
+    pub fn iter_standard(self) -> impl Iterator<Item = StandardCode> {
+        (0..2).flat_map(move |y| {
+            (0..2).map(move |x| StandardCode {
+                secondary: self.secondary,
+                y3: self.y3() + y,
+                x3: self.x3() + x,
+            })
         })
     }
 }
 
-impl Display for SecondaryCode {
+impl GridSquareCode for X2Code {
+    fn envelope(&self) -> LngLatBox {
+        self.secondary
+            .envelope()
+            .split_mn(5, 5, self.x as u32, self.y as u32)
+    }
+
+    #[inline]
+    fn index_xy(&self) -> (u32, u32) {
+        let (px, py) = self.secondary.index_xy();
+        (px * 5 + self.x as u32, py * 5 + self.y as u32)
+    }
+}
+
+impl FromStr for X2Code {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_ascii(s.as_bytes())
+    }
+}
+
+impl Display for X2Code {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{:02}{:02}{}{}",
-            self.primary.y, self.primary.x, self.y2, self.x2
-        )
+        write!(f, "{}{}{}5", self.secondary, self.y3(), self.x3())
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct StandardCode {
-    secondary: SecondaryCode,
+    pub(crate) secondary: SecondaryCode,
     /// "------Y-"
-    y3: u8,
+    pub(crate) y3: u8,
     /// "-------X"
-    x3: u8,
+    pub(crate) x3: u8,
 }
 
 impl StandardCode {
@@ -342,6 +854,19 @@ impl StandardCode {
         })
     }
 
+    /// Parses a code from exactly 8 ASCII digits
+    #[inline]
+    pub fn from_ascii(s: &[u8]) -> Result<Self, Error> {
+        let [secondary @ .., y3, x3] = s else {
+            return Err(Error::InvalidCode);
+        };
+        Ok(Self {
+            secondary: SecondaryCode::from_ascii(secondary)?,
+            y3: ascii_digit(*y3, 9)?,
+            x3: ascii_digit(*x3, 9)?,
+        })
+    }
+
     #[inline]
     pub fn y1(&self) -> u8 {
         self.secondary.y1()
@@ -418,22 +943,7 @@ impl FromStr for StandardCode {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 8 {
-            return Err(Error::InvalidCode);
-        }
-        let Some((secondary, rest)) = s.split_at_checked(6) else {
-            return Err(Error::InvalidCode);
-        };
-        let Some((y3_str, x3_str)) = rest.split_at_checked(1) else {
-            return Err(Error::InvalidCode);
-        };
-        let y3 = y3_str.parse::<u8>().map_err(|_| Error::InvalidCode)?;
-        let x3 = x3_str.parse::<u8>().map_err(|_| Error::InvalidCode)?;
-        Ok(Self {
-            secondary: SecondaryCode::from_str(secondary)?,
-            y3,
-            x3,
-        })
+        Self::from_ascii(s.as_bytes())
     }
 }
 
@@ -452,11 +962,11 @@ impl Display for StandardCode {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Quad<P: GridSquareCode> {
-    parent: P,
+    pub(crate) parent: P,
     /// 1-4
-    quad: u8,
+    pub(crate) quad: u8,
 }
 
 impl<P: GridSquareCode + Copy> Quad<P> {
@@ -518,6 +1028,22 @@ impl HalfCode {
         })
     }
 
+    /// Parses a code from exactly 9 ASCII digits
+    #[inline]
+    pub fn from_ascii(s: &[u8]) -> Result<Self, Error> {
+        let [parent @ .., quad] = s else {
+            return Err(Error::InvalidCode);
+        };
+        let quad = ascii_digit(*quad, 4)?;
+        if quad == 0 {
+            return Err(Error::InvalidCode);
+        }
+        Ok(Self {
+            parent: StandardCode::from_ascii(parent)?,
+            quad,
+        })
+    }
+
     pub fn y1(&self) -> u8 {
         self.parent.y1()
     }
@@ -555,20 +1081,7 @@ impl FromStr for HalfCode {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 9 {
-            return Err(Error::InvalidCode);
-        }
-        let Some((standard, rest)) = s.split_at_checked(8) else {
-            return Err(Error::InvalidCode);
-        };
-        let quad = rest.parse::<u8>().map_err(|_| Error::InvalidCode)?;
-        if !(1..=4).contains(&quad) {
-            return Err(Error::InvalidCode);
-        }
-        Ok(Self {
-            parent: StandardCode::from_str(standard)?,
-            quad,
-        })
+        Self::from_ascii(s.as_bytes())
     }
 }
 
@@ -593,6 +1106,22 @@ impl QuarterCode {
         })
     }
 
+    /// Parses a code from exactly 10 ASCII digits
+    #[inline]
+    pub fn from_ascii(s: &[u8]) -> Result<Self, Error> {
+        let [parent @ .., quad] = s else {
+            return Err(Error::InvalidCode);
+        };
+        let quad = ascii_digit(*quad, 4)?;
+        if quad == 0 {
+            return Err(Error::InvalidCode);
+        }
+        Ok(Self {
+            parent: HalfCode::from_ascii(parent)?,
+            quad,
+        })
+    }
+
     pub fn y1(&self) -> u8 {
         self.parent.y1()
     }
@@ -638,20 +1167,7 @@ impl FromStr for QuarterCode {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 10 {
-            return Err(Error::InvalidCode);
-        }
-        let Some((half, rest)) = s.split_at_checked(9) else {
-            return Err(Error::InvalidCode);
-        };
-        let quad = rest.parse::<u8>().map_err(|_| Error::InvalidCode)?;
-        if !(1..=4).contains(&quad) {
-            return Err(Error::InvalidCode);
-        }
-        Ok(Self {
-            parent: HalfCode::from_str(half)?,
-            quad,
-        })
+        Self::from_ascii(s.as_bytes())
     }
 }
 
@@ -676,6 +1192,22 @@ impl EighthCode {
         })
     }
 
+    /// Parses a code from exactly 11 ASCII digits
+    #[inline]
+    pub fn from_ascii(s: &[u8]) -> Result<Self, Error> {
+        let [parent @ .., quad] = s else {
+            return Err(Error::InvalidCode);
+        };
+        let quad = ascii_digit(*quad, 4)?;
+        if quad == 0 {
+            return Err(Error::InvalidCode);
+        }
+        Ok(Self {
+            parent: QuarterCode::from_ascii(parent)?,
+            quad,
+        })
+    }
+
     pub fn y1(&self) -> u8 {
         self.parent.y1()
     }
@@ -729,25 +1261,13 @@ impl FromStr for EighthCode {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 11 {
-            return Err(Error::InvalidCode);
-        }
-        let Some((half, rest)) = s.split_at_checked(10) else {
-            return Err(Error::InvalidCode);
-        };
-        let quad = rest.parse::<u8>().map_err(|_| Error::InvalidCode)?;
-        if !(1..=4).contains(&quad) {
-            return Err(Error::InvalidCode);
-        }
-        Ok(Self {
-            parent: QuarterCode::from_str(half)?,
-            quad,
-        })
+        Self::from_ascii(s.as_bytes())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::iterator::codes_in_box;
     use super::*;
 
     #[test]
@@ -771,6 +1291,8 @@ mod tests {
 
         assert_eq!(code.to_string(), "1234");
         PrimaryCode::from_str("12345").expect_err("must be 4 digits");
+        PrimaryCode::from_str("12+4").expect_err("must be digits");
+        assert_eq!(PrimaryCode::from_ascii(b"1234").unwrap(), code);
 
         let code = PrimaryCode::from_int(6441).unwrap();
         assert_eq!(
@@ -796,6 +1318,104 @@ mod tests {
         assert_eq!(code.index_xy(), (2, 1));
     }
 
+    #[test]
+    fn test_geometry() {
+        let code = StandardCode::from_str("53394611").unwrap();
+        let e = code.envelope();
+        assert!((code.center().lng() - 139.76875).abs() < 1e-12);
+        assert!((code.center().lat() - (35.0 + 40.0 / 60.0 + 45.0 / 3600.0)).abs() < 1e-12);
+        assert!(e.contains_point(code.center()));
+
+        let corners = code.corners();
+        assert_eq!(corners[0], e.min());
+        assert_eq!(corners[2], e.max());
+        assert_eq!(corners[1], LngLat::new(e.max().lng(), e.min().lat()));
+        assert_eq!(corners[3], LngLat::new(e.min().lng(), e.max().lat()));
+
+        let ring = code.ring(1);
+        assert_eq!(ring.len(), 5);
+        assert_eq!(&ring[..4], &corners);
+        assert_eq!(ring[4], ring[0]);
+        assert_eq!(code.ring(0), ring);
+
+        let ring = code.ring(4);
+        assert_eq!(ring.len(), 17);
+        assert_eq!(ring[4], corners[1]);
+        assert_eq!(ring[8], corners[2]);
+        assert_eq!(ring[12], corners[3]);
+        assert_eq!(ring[2].lat(), e.min().lat());
+        assert!((ring[2].lng() - code.center().lng()).abs() < 1e-12);
+        // the densified ring has the same area as the cell
+        let polygon = crate::gridsquare::Polygon::new(ring, vec![]);
+        assert!((polygon.area_m2() / code.area_m2() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_local() {
+        let p = LngLat::new(139.7705, 35.6701);
+        let code = HalfCode::from_lnglat(p).unwrap();
+        let (u, v) = code.to_local(p);
+        assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+        let q = code.at_local(u, v);
+        assert!((q.lng() - p.lng()).abs() < 1e-12 && (q.lat() - p.lat()).abs() < 1e-12);
+        assert_eq!(code.to_local(code.center()), (0.5, 0.5));
+        assert_eq!(code.at_local(0.0, 0.0), code.envelope().min());
+        assert_eq!(code.at_local(1.0, 1.0), code.envelope().max());
+
+        let points = code.sample_points(42).take(100).collect::<Vec<_>>();
+        assert!(
+            points
+                .iter()
+                .all(|p| HalfCode::from_lnglat(*p).unwrap() == code)
+        );
+        assert_eq!(points, code.sample_points(42).take(100).collect::<Vec<_>>());
+        assert_ne!(points, code.sample_points(43).take(100).collect::<Vec<_>>());
+        let other = HalfCode::from_index_xy(code.index_xy().0 + 1, code.index_xy().1).unwrap();
+        let (u0, v0) = code.to_local(points[0]);
+        let (u1, v1) = other.to_local(other.sample_points(42).next().unwrap());
+        assert!((u0 - u1).abs() + (v0 - v1).abs() > 1e-6);
+        // codes of different levels at the same index get different points too
+        let primary = PrimaryCode::from_index_xy(2, 1).unwrap();
+        let secondary = SecondaryCode::from_index_xy(2, 1).unwrap();
+        let (u0, v0) = primary.to_local(primary.sample_points(42).next().unwrap());
+        let (u1, v1) = secondary.to_local(secondary.sample_points(42).next().unwrap());
+        assert!((u0 - u1).abs() + (v0 - v1).abs() > 1e-6);
+
+        // the extreme positions stay in the code, whatever the rounding at its edges
+        let bounds = LngLatBox::new(LngLat::new(122.9, 20.4), LngLat::new(154.0, 45.6));
+        let (first, last) = (inset(0.0), inset(1.0f64.next_down()));
+        assert!(first > 0.0 && last < 1.0);
+        for code in codes_in_box::<QuarterCode>(bounds).step_by(9973) {
+            for (u, v) in [(first, first), (first, last), (last, first), (last, last)] {
+                assert_eq!(QuarterCode::from_lnglat(code.at_local(u, v)).unwrap(), code);
+            }
+        }
+        for code in codes_in_box::<EighthCode>(bounds).step_by(99991) {
+            for (u, v) in [(first, first), (last, last)] {
+                assert_eq!(EighthCode::from_lnglat(code.at_local(u, v)).unwrap(), code);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dimensions() {
+        // Tokyo: a standard code is about 1.13 km x 0.92 km
+        let code = StandardCode::from_lnglat(LngLat::new(139.767125, 35.681236)).unwrap();
+        assert!((code.width_m() - 1_130.0).abs() < 5.0, "{}", code.width_m());
+        assert!((code.height_m() - 924.9).abs() < 1.0, "{}", code.height_m());
+        assert!((code.area_m2() / (code.width_m() * code.height_m()) - 1.0).abs() < 1e-5);
+
+        let children = code.iter_half().map(|c| c.area_m2()).sum::<f64>();
+        assert!((children / code.area_m2() - 1.0).abs() < 1e-12);
+
+        // neighboring standard codes are about one cell apart
+        let east = StandardCode::from_str("53394612").unwrap();
+        let west = StandardCode::from_str("53394611").unwrap();
+        assert!((east.distance_m(&west) - west.width_m()).abs() < 1.0);
+        let north = StandardCode::from_str("53394621").unwrap();
+        assert!((north.distance_m(&west) - west.height_m()).abs() < 1.0);
+    }
+
     #[test]
     fn test_secondary_code() {
         assert!(matches!(
@@ -1065,4 +1685,150 @@ mod tests {
         assert_eq!(code.half(), HalfCode::from_int(644166893).unwrap());
         assert_eq!(code.quarter(), QuarterCode::from_int(6441668934).unwrap());
     }
+
+    #[test]
+    fn test_eighth_code_from_ascii() {
+        let code = EighthCode::from_int(12345678123).unwrap();
+        assert_eq!(EighthCode::from_ascii(b"12345678123").unwrap(), code);
+        EighthCode::from_ascii(b"12345678125").expect_err("quad must be 1-4");
+        EighthCode::from_ascii(b"12345678120").expect_err("quad must be 1-4");
+        EighthCode::from_ascii(b"12345878123").expect_err("y2 must be less than 8");
+        EighthCode::from_ascii(b"1234567812").expect_err("must be 11 digits");
+    }
+
+    #[test]
+    fn test_integrated_codes() {
+        assert!(matches!(
+            LevelAndCode::from_int(5339463),
+            Ok(LevelAndCode::X5(_))
+        ));
+        assert!(matches!(
+            LevelAndCode::from_str("533946245"),
+            Ok(LevelAndCode::X2(_))
+        ));
+        assert!(matches!(
+            LevelAndCode::from_int(533946244),
+            Ok(LevelAndCode::Half(_))
+        ));
+        X5Code::from_str("5339465").expect_err("the last digit must be 1-4");
+        X2Code::from_str("533946345").expect_err("digits must be even");
+        X2Code::from_int(533946244).expect_err("must end with 5");
+
+        let x5 = X5Code::from_str("5339463").unwrap();
+        assert_eq!(x5.quad(), 3);
+        assert_eq!(x5.to_string(), "5339463");
+        assert_eq!(x5.secondary(), SecondaryCode::from_int(533946).unwrap());
+        assert_eq!(X5Code::from_lnglat(x5.center()).unwrap(), x5);
+        let standards = x5.iter_standard().collect::<Vec<_>>();
+        assert_eq!(standards.len(), 25);
+        assert!(standards.is_sorted());
+        assert_eq!(standards[0].to_string(), "53394650");
+        assert_eq!(standards[24].to_string(), "53394694");
+        let union = standards
+            .iter()
+            .fold(standards[0].envelope(), |b, c| b.union(&c.envelope()));
+        assert_eq!(union, x5.envelope());
+
+        let x2 = X2Code::from_int(533946245).unwrap();
+        assert_eq!((x2.y3(), x2.x3()), (2, 4));
+        assert_eq!(x2.to_string(), "533946245");
+        assert_eq!(X2Code::from_lnglat(x2.center()).unwrap(), x2);
+        assert_eq!(
+            x2.iter_standard()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>(),
+            ["53394624", "53394625", "53394634", "53394635"]
+        );
+        let e = x2.envelope();
+        assert_eq!(
+            e.min,
+            StandardCode::from_int(53394624).unwrap().envelope().min
+        );
+        assert_eq!(
+            e.max,
+            StandardCode::from_int(53394635).unwrap().envelope().max
+        );
+
+        // integrated codes contain the standards inside them, but not each other
+        let standard = LevelAndCode::from_str("53394635").unwrap();
+        assert_eq!(
+            standard.ancestor(Level::X2).unwrap().to_string(),
+            "533946245"
+        );
+        assert_eq!(standard.ancestor(Level::X5).unwrap().to_string(), "5339462");
+        assert_eq!(LevelAndCode::from(x2).ancestor(Level::X5), None);
+        assert_eq!(
+            LevelAndCode::from(x2).parent().unwrap().to_string(),
+            "533946"
+        );
+        assert_eq!(LevelAndCode::from(x5).children().count(), 0);
+    }
+
+    #[test]
+    fn test_level_and_code() {
+        let code = LevelAndCode::from_str("64414278234").unwrap();
+        assert_eq!(code.level(), Level::Eighth);
+        assert_eq!(code.to_int(), 64414278234);
+        assert_eq!(code.to_string(), "64414278234");
+        assert_eq!(
+            code.envelope(),
+            EighthCode::from_int(64414278234).unwrap().envelope()
+        );
+
+        let ancestors = std::iter::successors(Some(code), |c| c.parent())
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ancestors,
+            [
+                "64414278234",
+                "6441427823",
+                "644142782",
+                "64414278",
+                "644142",
+                "6441"
+            ]
+        );
+        assert_eq!(
+            code.ancestor(Level::Secondary).unwrap().to_string(),
+            "644142"
+        );
+        assert!(code.ancestor(Level::Eighth).is_some());
+
+        let primary = LevelAndCode::from_str("6441").unwrap();
+        assert!(primary.contains(&code));
+        assert!(code.contains(&code));
+        assert!(!code.contains(&primary));
+        assert!(!LevelAndCode::from_str("6442").unwrap().contains(&code));
+
+        let children = primary.children().collect::<Vec<_>>();
+        assert_eq!(children.len(), 64);
+        assert!(children.is_sorted());
+        assert!(children.iter().all(|c| primary.contains(c)));
+        assert_eq!(code.children().count(), 0);
+
+        // string order
+        let mut codes = [
+            "6441427823",
+            "644142",
+            "64414278",
+            "6441",
+            "6442",
+            "644143",
+            "644142782",
+        ]
+        .map(|s| LevelAndCode::from_str(s).unwrap());
+        codes.sort();
+        let sorted = codes.map(|c| c.to_string());
+        let mut expected = sorted.clone();
+        expected.sort();
+        assert_eq!(sorted, expected);
+
+        assert_eq!(
+            LevelAndCode::from_lnglat(Level::Half, LngLat::new(141.8686782, 43.2405564))
+                .unwrap()
+                .to_string(),
+            "644166893"
+        );
+    }
 }
@@ -0,0 +1,220 @@
+// Connected components of sets of codes
+
+use super::*;
+use std::collections::HashSet;
+
+/// Which codes are adjacent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Connectivity {
+    /// Codes sharing an edge
+    Four,
+    /// Codes sharing an edge or a corner
+    Eight,
+}
+
+impl Connectivity {
+    /// Returns the offsets of the adjacent cells: east, north, west and south, followed by the
+    /// diagonal ones
+    pub(crate) fn offsets(self) -> &'static [(i32, i32)] {
+        const OFFSETS: [(i32, i32); 8] = [
+            (1, 0),
+            (0, 1),
+            (-1, 0),
+            (0, -1),
+            (1, 1),
+            (-1, 1),
+            (-1, -1),
+            (1, -1),
+        ];
+        match self {
+            Connectivity::Four => &OFFSETS[..4],
+            Connectivity::Eight => &OFFSETS,
+        }
+    }
+}
+
+/// A connected component
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Component {
+    /// Number of codes in the component
+    pub size: usize,
+    /// Union of the envelopes of the codes
+    pub bounds: LngLatBox,
+}
+
+/// Connected components of a set of codes of level `L`
+///
+/// Components are numbered from 0 in the code order of their first code.
+#[derive(Debug, Clone)]
+pub struct Components<L> {
+    /// Codes with their component, sorted by code
+    labels: Vec<(L, u32)>,
+    /// Codes grouped by component, each group sorted by code
+    members: Vec<L>,
+    /// Start of the group of each component in `members`, followed by the total
+    offsets: Vec<usize>,
+    components: Vec<Component>,
+}
+
+/// Groups the codes into components connected across parent boundaries
+pub fn connected_components<L: MeshLevel>(
+    codes: impl IntoIterator<Item = L>,
+    connectivity: Connectivity,
+) -> Components<L> {
+    let mut sorted = codes.into_iter().collect::<Vec<_>>();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let mut unvisited = sorted.iter().copied().collect::<HashSet<_>>();
+
+    let mut labels = Vec::with_capacity(sorted.len());
+    let mut components = Vec::new();
+    let mut stack = Vec::new();
+    for code in sorted {
+        if !unvisited.remove(&code) {
+            continue;
+        }
+        let id = components.len() as u32;
+        let mut component = Component {
+            size: 0,
+            bounds: code.envelope(),
+        };
+        stack.push(code);
+        while let Some(code) = stack.pop() {
+            labels.push((code, id));
+            component.size += 1;
+            component.bounds = component.bounds.union(&code.envelope());
+            stack.extend(code.neighbors(connectivity).filter(|n| unvisited.remove(n)));
+        }
+        components.push(component);
+    }
+    labels.sort_unstable_by_key(|(code, _)| *code);
+
+    let mut grouped = labels.clone();
+    grouped.sort_unstable_by_key(|(code, id)| (*id, *code));
+    let mut offsets = vec![0];
+    offsets.extend(components.iter().scan(0, |end, c| {
+        *end += c.size;
+        Some(*end)
+    }));
+    Components {
+        labels,
+        members: grouped.into_iter().map(|(code, _)| code).collect(),
+        offsets,
+        components,
+    }
+}
+
+impl<L: MeshLevel> Components<L> {
+    /// Returns the number of components
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// Returns the components, indexed by their ids
+    pub fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    /// Returns the id of the component containing the code, or None if it is not in the set
+    pub fn label(&self, code: L) -> Option<usize> {
+        self.labels
+            .binary_search_by_key(&code, |(c, _)| *c)
+            .ok()
+            .map(|i| self.labels[i].1 as usize)
+    }
+
+    /// Returns the codes with their component ids, in code order
+    pub fn iter(&self) -> impl Iterator<Item = (L, usize)> + '_ {
+        self.labels.iter().map(|(c, id)| (*c, *id as usize))
+    }
+
+    /// Returns the codes of a component, in code order
+    pub fn codes_of(&self, id: usize) -> impl Iterator<Item = L> + Clone + '_ {
+        let codes = match self.offsets.get(id..id.saturating_add(2)) {
+            Some(&[start, end]) => &self.members[start..end],
+            _ => &[],
+        };
+        codes.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_components() {
+        let standard = |s: &str| StandardCode::from_str(s).unwrap();
+        // a diagonal pair crossing a primary corner, a row of three crossing a secondary
+        // boundary, and a single code
+        let codes = [
+            standard("53397799"),
+            standard("54400000"),
+            standard("53394619"),
+            standard("53394710"),
+            standard("53394711"),
+            standard("64414278"),
+        ];
+
+        let four = connected_components(codes, Connectivity::Four);
+        assert_eq!(four.len(), 4);
+        assert_eq!(
+            four.components().iter().map(|c| c.size).collect::<Vec<_>>(),
+            [3, 1, 1, 1]
+        );
+        assert_eq!(four.label(standard("53394619")), Some(0));
+        assert_eq!(four.label(standard("53394711")), Some(0));
+        assert_eq!(
+            four.label(standard("53394710")),
+            four.label(standard("53394711"))
+        );
+        assert_eq!(four.label(standard("53394712")), None);
+        assert_ne!(
+            four.label(standard("53397799")),
+            four.label(standard("54400000"))
+        );
+
+        let eight = connected_components(codes, Connectivity::Eight);
+        assert_eq!(eight.len(), 3);
+        let id = eight.label(standard("53397799")).unwrap();
+        assert_eq!(eight.label(standard("54400000")), Some(id));
+        let component = eight.components()[id];
+        assert_eq!(component.size, 2);
+        assert_eq!(
+            component.bounds,
+            standard("53397799")
+                .envelope()
+                .union(&standard("54400000").envelope())
+        );
+        assert_eq!(
+            eight.codes_of(id).collect::<Vec<_>>(),
+            [standard("53397799"), standard("54400000")]
+        );
+        assert_eq!(eight.iter().count(), codes.len());
+        assert_eq!(eight.codes_of(eight.len()).count(), 0);
+        for (id, component) in four.components().iter().enumerate() {
+            let members = four.codes_of(id).collect::<Vec<_>>();
+            assert_eq!(members.len(), component.size);
+            assert!(members.is_sorted());
+            assert!(members.iter().all(|c| four.label(*c) == Some(id)));
+        }
+    }
+
+    #[test]
+    fn test_components_eighth() {
+        // the ring of eighth codes around a standard code is one component
+        let center = StandardCode::from_str("53394611").unwrap().envelope();
+        let ring = codes_in_box::<EighthCode>(center)
+            .filter(|c| !center.contains_box(&c.envelope()))
+            .collect::<Vec<_>>();
+        assert_eq!(ring.len(), 10 * 10 - 8 * 8);
+        let components = connected_components(ring.iter().copied(), Connectivity::Four);
+        assert_eq!(components.len(), 1);
+        assert_eq!(components.components()[0].size, ring.len());
+    }
+}
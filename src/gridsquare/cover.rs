@@ -0,0 +1,273 @@
+// Multi-level adaptive cover (similar to S2's RegionCoverer)
+
+use super::*;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+
+/// Covers a region with codes of mixed levels: coarse codes where the region is fully inside,
+/// finer codes along its edge
+#[derive(Debug, Clone, Copy)]
+pub struct Coverer {
+    /// Codes coarser than this level are never used
+    pub min_level: Level,
+    /// Codes finer than this level are never used
+    pub max_level: Level,
+    /// Soft limit on the number of codes
+    ///
+    /// The limit is exceeded only when the region intersects more than `max_cells` codes at `min_level`.
+    pub max_cells: usize,
+}
+
+impl Default for Coverer {
+    fn default() -> Self {
+        Self {
+            min_level: Level::Primary,
+            max_level: Level::Eighth,
+            max_cells: 8,
+        }
+    }
+}
+
+impl Coverer {
+    /// Returns the codes covering the region, sorted in code order and without overlaps
+    ///
+    /// `bounds` must enclose the region.
+    pub fn cover(&self, region: &impl Boundary, bounds: LngLatBox) -> Vec<LevelAndCode> {
+        let min_level = self.min_level.min(self.max_level);
+        let mut result = Vec::new();
+        let mut queue = BinaryHeap::new();
+        let mut initial = Vec::new();
+        for prim in primaries_from_bounds(bounds) {
+            collect_initial(region, prim.into(), min_level, &mut initial);
+        }
+        for (code, coverage) in initial {
+            if coverage == Coverage::Full || code.level() >= self.max_level {
+                result.push(code);
+            } else {
+                queue.push(Reverse((code.level(), code)));
+            }
+        }
+
+        // refine the coarsest candidates first, and stop at the first one exceeding the budget
+        // so that the codes along the edge of the region stay within one level of each other
+        while let Some(Reverse((_, code))) = queue.pop() {
+            let children = code
+                .children()
+                .filter_map(|c| region.coverage(&c.envelope()).map(|cov| (c, cov)))
+                .collect::<Vec<_>>();
+            if result.len() + queue.len() + children.len() > self.max_cells {
+                result.push(code);
+                result.extend(queue.drain().map(|Reverse((_, c))| c));
+                break;
+            }
+            for (child, coverage) in children {
+                if coverage == Coverage::Full || child.level() >= self.max_level {
+                    result.push(child);
+                } else {
+                    queue.push(Reverse((child.level(), child)));
+                }
+            }
+        }
+        let mut codes = result.into_iter().collect();
+        merge_siblings(&mut codes, min_level);
+        codes.into_iter().collect()
+    }
+
+    /// Returns the codes covering the polygons
+    pub fn cover_polygon(&self, polygon: &MultiPolygon) -> Vec<LevelAndCode> {
+        match polygon.bounds() {
+            Some(bounds) => self.cover(polygon, bounds),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the codes covering the box, excluding codes merely touching its edge
+    pub fn cover_box(&self, bounds: LngLatBox) -> Vec<LevelAndCode> {
+        self.cover(&Polygon::new(bounds.corners().to_vec(), vec![]), bounds)
+    }
+}
+
+/// Collects the codes at `level` under `code` that intersect the region
+fn collect_initial(
+    region: &impl Boundary,
+    code: LevelAndCode,
+    level: Level,
+    out: &mut Vec<(LevelAndCode, Coverage)>,
+) {
+    let Some(coverage) = region.coverage(&code.envelope()) else {
+        return;
+    };
+    if code.level() >= level {
+        out.push((code, coverage));
+    } else if coverage == Coverage::Full {
+        let mut stack = vec![code];
+        while let Some(c) = stack.pop() {
+            if c.level() >= level {
+                out.push((c, Coverage::Full));
+            } else {
+                stack.extend(c.children());
+            }
+        }
+    } else {
+        for child in code.children() {
+            collect_initial(region, child, level, out);
+        }
+    }
+}
+
+/// Replaces complete sets of siblings with their parent, down to `min_level`
+pub(crate) fn merge_siblings(codes: &mut BTreeSet<LevelAndCode>, min_level: Level) {
+    for level in Level::ALL.into_iter().rev() {
+        if level <= min_level {
+            break;
+        }
+        let siblings = level.split() * level.split();
+        let mut counts: HashMap<LevelAndCode, u32> = HashMap::new();
+        for code in codes.iter().filter(|c| c.level() == level) {
+            *counts.entry(code.parent().unwrap()).or_default() += 1;
+        }
+        for (parent, _) in counts.into_iter().filter(|(_, n)| *n == siblings) {
+            for child in parent.children() {
+                codes.remove(&child);
+            }
+            codes.insert(parent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn assert_no_overlaps(codes: &[LevelAndCode]) {
+        for (i, a) in codes.iter().enumerate() {
+            for b in &codes[i + 1..] {
+                assert!(!a.contains(b) && !b.contains(a), "{a} and {b} overlap");
+            }
+        }
+    }
+
+    #[test]
+    fn test_cover_box() {
+        let bounds = LngLatBox::new(
+            LngLat::new(141.305438074, 42.939466350),
+            LngLat::new(141.563765511, 43.129434849),
+        );
+        for max_cells in [1, 8, 50, 200] {
+            let coverer = Coverer {
+                max_cells,
+                ..Default::default()
+            };
+            let codes = coverer.cover_box(bounds);
+            assert!(
+                codes.len() <= max_cells.max(1),
+                "{max_cells}: {}",
+                codes.len()
+            );
+            assert!(codes.is_sorted());
+            assert_no_overlaps(&codes);
+            // every point of the box is covered
+            for (x, y) in [(0.01, 0.01), (0.5, 0.5), (0.99, 0.2), (0.3, 0.99)] {
+                let p = LngLat::new(
+                    141.305438074 + (141.563765511 - 141.305438074) * x,
+                    42.939466350 + (43.129434849 - 42.939466350) * y,
+                );
+                assert!(codes.iter().any(|c| c.envelope().contains_point(p)));
+            }
+        }
+
+        let coverer = Coverer {
+            min_level: Level::Secondary,
+            max_level: Level::Standard,
+            max_cells: 1000,
+        };
+        let codes = coverer.cover_box(bounds);
+        assert_no_overlaps(&codes);
+        assert!(codes.iter().any(|c| c.level() == Level::Secondary));
+        let standards: u32 = codes
+            .iter()
+            .map(|c| match c.level() {
+                Level::Secondary => 100,
+                Level::Standard => 1,
+                _ => unreachable!(),
+            })
+            .sum();
+        let expected = codes_in_box::<StandardCode>(bounds)
+            .filter(|c| boundary::overlaps(&c.envelope(), &bounds))
+            .count();
+        assert_eq!(standards as usize, expected);
+    }
+
+    #[test]
+    fn test_cover_polygon() {
+        // a polygon exactly equal to a secondary code, with a standard code as a hole
+        let secondary = SecondaryCode::from_str("644142").unwrap();
+        let hole = StandardCode::from_str("64414255").unwrap();
+        let polygon = MultiPolygon::from(Polygon::new(secondary.ring(1), vec![hole.ring(1)]));
+
+        let coverer = Coverer {
+            max_cells: 1000,
+            ..Default::default()
+        };
+        let codes = coverer.cover_polygon(&polygon);
+        assert_eq!(codes.len(), 99);
+        assert!(codes.iter().all(|c| c.level() == Level::Standard));
+
+        let coverer = Coverer {
+            min_level: Level::Secondary,
+            max_level: Level::Secondary,
+            max_cells: 1,
+        };
+        let codes = coverer.cover_polygon(&polygon);
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].to_string(), "644142");
+
+        assert!(
+            Coverer::default()
+                .cover_polygon(&MultiPolygon::default())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_cover_budget() {
+        // a triangle over a primary code, refined until the budget runs out
+        let envelope = PrimaryCode::from_str("5339").unwrap().envelope();
+        let triangle = Polygon::new(envelope.corners()[..3].to_vec(), vec![]);
+        for max_cells in [10, 40, 100, 300] {
+            let coverer = Coverer {
+                max_cells,
+                ..Default::default()
+            };
+            let codes = coverer.cover(&triangle, envelope);
+            assert!(codes.len() <= max_cells);
+            assert_no_overlaps(&codes);
+            // the codes along the diagonal are refined evenly
+            let partial = codes
+                .iter()
+                .filter(|c| triangle.coverage(&c.envelope()) == Some(Coverage::Partial))
+                .map(|c| Level::ALL.iter().position(|l| *l == c.level()).unwrap())
+                .collect::<Vec<_>>();
+            let (coarsest, finest) = (partial.iter().min(), partial.iter().max());
+            assert!(
+                finest.unwrap() - coarsest.unwrap() <= 1,
+                "{max_cells}: {partial:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_siblings() {
+        let parent = LevelAndCode::from_str("64414278").unwrap();
+        let mut codes = parent.children().collect::<Vec<_>>();
+        codes.push(LevelAndCode::from_str("53394611").unwrap());
+        let mut merged = codes.iter().copied().collect();
+        merge_siblings(&mut merged, Level::Primary);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged.last(), Some(&parent));
+        let mut merged = codes.into_iter().collect();
+        merge_siblings(&mut merged, Level::Half);
+        assert_eq!(merged.len(), 5);
+    }
+}
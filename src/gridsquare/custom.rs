@@ -0,0 +1,220 @@
+// Custom subdivisions of grid square codes
+
+use super::*;
+use crate::Error;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A code made by dividing a parent code into `M` columns and `N` rows
+///
+/// It is written as the parent code followed by the row and the column, counted from the
+/// south-west corner starting at 0, each zero-padded to `W` digits. For example,
+/// `CustomSubdivision<StandardCode, 3, 3>` divides standard codes into thirds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CustomSubdivision<P, const M: u8, const N: u8, const W: usize = 1> {
+    parent: P,
+    y: u8,
+    x: u8,
+}
+
+impl<P, const M: u8, const N: u8, const W: usize> CustomSubdivision<P, M, N, W> {
+    /// Fails to compile where codes are made if a row or column does not fit in `W` digits
+    const VALID: () = assert!(
+        M > 0
+            && N > 0
+            && W > 0
+            && (W >= 3 || (M as u32) <= 10u32.pow(W as u32) && (N as u32) <= 10u32.pow(W as u32)),
+        "M and N must be between 1 and 10^W"
+    );
+}
+
+impl<P: GridSquareCode + Copy, const M: u8, const N: u8, const W: usize>
+    CustomSubdivision<P, M, N, W>
+{
+    pub fn new(parent: P, x: u8, y: u8) -> Result<Self, Error> {
+        let () = Self::VALID;
+        if x >= M || y >= N {
+            return Err(Error::InvalidCode);
+        }
+        Ok(Self { parent, y, x })
+    }
+
+    #[inline]
+    pub fn parent(&self) -> P {
+        self.parent
+    }
+
+    /// Returns the column, counted from the west
+    #[inline]
+    pub fn x(&self) -> u8 {
+        self.x
+    }
+
+    /// Returns the row, counted from the south
+    #[inline]
+    pub fn y(&self) -> u8 {
+        self.y
+    }
+
+    /// Returns the codes dividing the parent, in code order
+    pub fn iter_children_of(parent: P) -> impl Iterator<Item = Self> {
+        let () = Self::VALID;
+        (0..N).flat_map(move |y| (0..M).map(move |x| Self { parent, y, x }))
+    }
+}
+
+impl<P: MeshLevel, const M: u8, const N: u8, const W: usize> CustomSubdivision<P, M, N, W> {
+    pub fn from_lnglat(lnglat: LngLat) -> Result<Self, Error> {
+        let () = Self::VALID;
+        let parent = P::from_lnglat(lnglat)?;
+        let x = (lnglat.vlng / 30. * (P::DIVISIONS * M as u32) as f64) as u64 % M as u64;
+        let y = (lnglat.vlat / 20. * (P::DIVISIONS * N as u32) as f64) as u64 % N as u64;
+        Ok(Self {
+            parent,
+            y: y as u8,
+            x: x as u8,
+        })
+    }
+
+    pub fn from_index_xy(x: u32, y: u32) -> Result<Self, Error> {
+        let () = Self::VALID;
+        Ok(Self {
+            parent: P::from_index_xy(x / M as u32, y / N as u32)?,
+            y: (y % N as u32) as u8,
+            x: (x % M as u32) as u8,
+        })
+    }
+}
+
+impl<P: GridSquareCode, const M: u8, const N: u8, const W: usize> GridSquareCode
+    for CustomSubdivision<P, M, N, W>
+{
+    fn envelope(&self) -> LngLatBox {
+        self.parent
+            .envelope()
+            .split_mn(M as u32, N as u32, self.x as u32, self.y as u32)
+    }
+
+    #[inline]
+    fn index_xy(&self) -> (u32, u32) {
+        let (px, py) = self.parent.index_xy();
+        (px * M as u32 + self.x as u32, py * N as u32 + self.y as u32)
+    }
+}
+
+impl<P: Display, const M: u8, const N: u8, const W: usize> Display
+    for CustomSubdivision<P, M, N, W>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{:0w$}{:0w$}", self.parent, self.y, self.x, w = W)
+    }
+}
+
+impl<P, const M: u8, const N: u8, const W: usize> FromStr for CustomSubdivision<P, M, N, W>
+where
+    P: GridSquareCode + Copy + FromStr<Err = Error>,
+{
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s.len().checked_sub(2 * W).ok_or(Error::InvalidCode)?;
+        if W == 0 || !s.is_char_boundary(split) {
+            return Err(Error::InvalidCode);
+        }
+        let (parent, suffix) = s.split_at(split);
+        let digits = |s: &str| {
+            s.bytes().try_fold(0u32, |acc, b| {
+                b.is_ascii_digit()
+                    .then(|| acc.saturating_mul(10).saturating_add((b - b'0') as u32))
+                    .ok_or(Error::InvalidCode)
+            })
+        };
+        let y = digits(&suffix[..W])?;
+        let x = digits(&suffix[W..])?;
+        if x >= M as u32 || y >= N as u32 {
+            return Err(Error::InvalidCode);
+        }
+        Self::new(P::from_str(parent)?, x as u8, y as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Third = CustomSubdivision<StandardCode, 3, 3>;
+    type FiveByFour = CustomSubdivision<HalfCode, 5, 4, 2>;
+
+    #[test]
+    fn test_custom_subdivision() {
+        let standard = StandardCode::from_str("53394611").unwrap();
+        let children = Third::iter_children_of(standard).collect::<Vec<_>>();
+        assert_eq!(children.len(), 9);
+        assert!(children.windows(2).all(|w| w[0] < w[1]));
+        let union = children
+            .iter()
+            .map(|c| c.envelope())
+            .reduce(|a, b| a.union(&b))
+            .unwrap();
+        assert_eq!(union, standard.envelope());
+
+        let code = Third::new(standard, 2, 1).unwrap();
+        assert_eq!(code.to_string(), "5339461112");
+        assert_eq!(Third::from_str("5339461112").unwrap(), code);
+        assert_eq!(Third::from_lnglat(code.center()).unwrap(), code);
+        let (x, y) = code.index_xy();
+        assert_eq!(Third::from_index_xy(x, y).unwrap(), code);
+        assert_eq!(
+            (x / 3, y / 3),
+            standard.index_xy(),
+            "index is the parent index scaled by the split factors"
+        );
+        assert!(Third::new(standard, 3, 0).is_err());
+        assert!(Third::from_str("5339461113").is_err());
+        assert!(Third::from_str("53394611").is_err());
+        assert!(Third::from_str("533946111").is_err());
+
+        let half = HalfCode::from_str("533946113").unwrap();
+        let code = FiveByFour::new(half, 4, 3).unwrap();
+        assert_eq!(code.to_string(), "5339461130304");
+        assert_eq!(FiveByFour::from_str("5339461130304").unwrap(), code);
+        assert!(FiveByFour::from_str("5339461130305").is_err());
+        assert!(FiveByFour::from_str("53394611303a4").is_err());
+        let e = code.envelope();
+        assert!((e.width() - half.envelope().width() / 5.0).abs() < 1e-12);
+        assert!((e.height() - half.envelope().height() / 4.0).abs() < 1e-12);
+        assert_eq!(e.max(), half.envelope().max());
+        for p in code.sample_points(1).take(20) {
+            assert_eq!(FiveByFour::from_lnglat(p).unwrap(), code);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        fn check<P, const M: u8, const N: u8, const W: usize>(parent: P)
+        where
+            P: MeshLevel + Display + FromStr<Err = Error>,
+        {
+            let children = CustomSubdivision::<P, M, N, W>::iter_children_of(parent);
+            let mut count = 0;
+            for code in children {
+                let s = code.to_string();
+                assert_eq!(s.len(), parent.to_string().len() + 2 * W);
+                assert_eq!(CustomSubdivision::from_str(&s).unwrap(), code);
+                let (x, y) = code.index_xy();
+                assert_eq!(CustomSubdivision::from_index_xy(x, y).unwrap(), code);
+                assert_eq!(CustomSubdivision::from_lnglat(code.center()).unwrap(), code);
+                count += 1;
+            }
+            assert_eq!(count, M as usize * N as usize);
+        }
+        let standard = StandardCode::from_str("53394611").unwrap();
+        check::<_, 1, 1, 1>(standard);
+        check::<_, 3, 3, 1>(standard);
+        check::<_, 10, 10, 1>(standard);
+        check::<_, 7, 2, 2>(standard);
+        check::<_, 100, 100, 2>(standard);
+        check::<_, 12, 250, 3>(HalfCode::from_str("533946113").unwrap());
+        check::<_, 2, 2, 1>(PrimaryCode::from_str("6441").unwrap());
+    }
+}
@@ -0,0 +1,339 @@
+// Dissolving sets of codes into outline polygons
+
+use super::*;
+use std::collections::HashMap;
+
+/// A vertex of the lattice of the finest level involved
+type Point = (i64, i64);
+
+/// Returns the outline of the union of the codes as polygons with holes
+///
+/// Codes may be of mixed levels and may overlap. The outline is exact on the mesh lattice
+/// and has no redundant vertices. Each polygon is one edge-connected component; codes
+/// touching only at a corner end up in different polygons.
+pub fn dissolve<C: Into<LevelAndCode>>(codes: impl IntoIterator<Item = C>) -> MultiPolygon {
+    MultiPolygon(
+        dissolve_components(codes)
+            .into_iter()
+            .map(|(polygon, _)| polygon)
+            .collect(),
+    )
+}
+
+/// Same as [`dissolve`], keeping each connected component separate along with the codes forming it
+///
+/// The codes are those of the normalized set (see [`MeshSet`]).
+pub fn dissolve_components<C: Into<LevelAndCode>>(
+    codes: impl IntoIterator<Item = C>,
+) -> Vec<(Polygon, Vec<LevelAndCode>)> {
+    let set = codes.into_iter().collect::<MeshSet>();
+    let Some(finest) = set.iter().map(|c| c.level()).max() else {
+        return Vec::new();
+    };
+    let d = finest.divisions() as i64;
+    let codes = set.iter().collect::<Vec<_>>();
+
+    // boundary edges with the index of the code on their left; shared edges cancel out and
+    // join the codes on both sides into one group
+    let mut edges: HashMap<(Point, Point), usize> = HashMap::new();
+    let mut groups = (0..codes.len()).collect::<Vec<_>>();
+    for (i, code) in codes.iter().enumerate() {
+        let (x0, y0, x1, y1) = lattice_box(*code, d);
+        let corners = [(x0, y0), (x1, y0), (x1, y1), (x0, y1)];
+        for k in 0..4 {
+            let (a, b) = (corners[k], corners[(k + 1) % 4]);
+            let step = ((b.0 - a.0).signum(), (b.1 - a.1).signum());
+            let mut p = a;
+            while p != b {
+                let q = (p.0 + step.0, p.1 + step.1);
+                match edges.remove(&(q, p)) {
+                    Some(j) => join(&mut groups, i, j),
+                    None => {
+                        edges.insert((p, q), i);
+                    }
+                }
+                p = q;
+            }
+        }
+    }
+
+    let mut outgoing: HashMap<Point, Vec<Point>> = HashMap::new();
+    for (a, b) in edges.keys() {
+        outgoing.entry(*a).or_default().push(*b);
+    }
+    let mut starts = edges.keys().copied().collect::<Vec<_>>();
+    starts.sort_unstable();
+
+    let (mut exteriors, mut holes) = (Vec::new(), Vec::new());
+    for (a, b) in starts {
+        if !outgoing.get(&a).is_some_and(|out| out.contains(&b)) {
+            continue;
+        }
+        for ring in split_loops(trace_ring(&mut outgoing, a, b)) {
+            if ring_area2(&ring) > 0 {
+                exteriors.push(ring);
+            } else {
+                holes.push(ring);
+            }
+        }
+    }
+
+    // no ring separates codes sharing an edge, so each group of codes lies inside the exterior
+    // along its edge, and so do the holes along its edge
+    let mut group_of = |ring: &[Point]| {
+        let (a, b) = (ring[0], ring[1]);
+        let q = (a.0 + (b.0 - a.0).signum(), a.1 + (b.1 - a.1).signum());
+        root(&mut groups, edges[&(a, q)])
+    };
+    let owners = exteriors
+        .iter()
+        .enumerate()
+        .map(|(i, ring)| (group_of(ring), i))
+        .collect::<HashMap<_, _>>();
+    let mut components = vec![(Vec::new(), Vec::new()); exteriors.len()];
+    for hole in holes {
+        components[owners[&group_of(&hole)]].0.push(hole);
+    }
+    for (i, code) in codes.into_iter().enumerate() {
+        components[owners[&root(&mut groups, i)]].1.push(code);
+    }
+
+    let to_lnglat = |ring: Vec<Point>| {
+        ring.into_iter()
+            .map(|(x, y)| {
+                LngLat::new_raw(
+                    (x as f64 / d as f64 + 100.) * 30.,
+                    y as f64 / d as f64 * 20.,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    exteriors
+        .into_iter()
+        .zip(components)
+        .map(|(exterior, (holes, codes))| {
+            let polygon = Polygon::new(
+                to_lnglat(exterior),
+                holes.into_iter().map(to_lnglat).collect(),
+            );
+            (polygon, codes)
+        })
+        .collect()
+}
+
+/// Returns the representative of the group of `i`
+fn root(groups: &mut [usize], mut i: usize) -> usize {
+    while groups[i] != i {
+        groups[i] = groups[groups[i]];
+        i = groups[i];
+    }
+    i
+}
+
+/// Merges the groups of `i` and `j`
+fn join(groups: &mut [usize], i: usize, j: usize) {
+    let (a, b) = (root(groups, i), root(groups, j));
+    groups[a.max(b)] = a.min(b);
+}
+
+/// Returns the extent of the code on the lattice with `d` divisions per primary code
+fn lattice_box(code: LevelAndCode, d: i64) -> (i64, i64, i64, i64) {
+    let r = d / code.level().divisions() as i64;
+    let (x, y) = code.index_xy();
+    let (x0, y0) = (x as i64 * r, y as i64 * r);
+    (x0, y0, x0 + r, y0 + r)
+}
+
+/// Follows boundary edges from (a, b) back to it, removing them, and returns the corners of the ring
+/// (the start may be in the middle of an edge)
+///
+/// Where two rings meet at a vertex the sharpest left turn is taken, which keeps the codes on the
+/// left of the ring connected through edges.
+fn trace_ring(outgoing: &mut HashMap<Point, Vec<Point>>, a: Point, b: Point) -> Vec<Point> {
+    let mut ring = vec![a];
+    let (mut prev, mut cur) = (a, b);
+    loop {
+        // the first edge is kept until the end to recognize where the ring closes
+        if prev != a || cur != b {
+            let out = outgoing.get_mut(&prev).expect("edge exists");
+            out.retain(|p| *p != cur);
+        }
+        let dir = (cur.0 - prev.0, cur.1 - prev.1);
+        let out = &outgoing[&cur];
+        let left = (cur.0 - dir.1, cur.1 + dir.0);
+        let next = if out.contains(&left) { left } else { out[0] };
+        if cur == a && next == b {
+            outgoing
+                .get_mut(&a)
+                .expect("edge exists")
+                .retain(|p| *p != b);
+            break;
+        }
+        if (next.0 - cur.0, next.1 - cur.1) != dir {
+            ring.push(cur);
+        }
+        (prev, cur) = (cur, next);
+    }
+    ring
+}
+
+/// Splits a ring touching itself at vertices into simple rings without redundant vertices
+///
+/// This separates holes touching the exterior at a corner, as required for valid polygons.
+fn split_loops(ring: Vec<Point>) -> Vec<Vec<Point>> {
+    let mut loops = Vec::new();
+    let mut path: Vec<Point> = Vec::with_capacity(ring.len());
+    let mut seen: HashMap<Point, usize> = HashMap::new();
+    for p in ring {
+        if let Some(&i) = seen.get(&p) {
+            let rest = path.split_off(i + 1);
+            for q in &rest {
+                seen.remove(q);
+            }
+            loops.push([vec![p], rest].concat());
+        } else {
+            seen.insert(p, path.len());
+            path.push(p);
+        }
+    }
+    loops.push(path);
+    loops
+        .into_iter()
+        .map(|ring| {
+            let n = ring.len();
+            (0..n)
+                .filter(|i| {
+                    let (p, q, r) = (ring[(i + n - 1) % n], ring[*i], ring[(i + 1) % n]);
+                    (q.0 - p.0) * (r.1 - q.1) != (q.1 - p.1) * (r.0 - q.0)
+                })
+                .map(|i| ring[i])
+                .collect::<Vec<_>>()
+        })
+        .filter(|ring| ring.len() >= 4)
+        .collect()
+}
+
+/// Returns twice the signed area of the ring (positive when counter-clockwise)
+fn ring_area2(ring: &[Point]) -> i64 {
+    (0..ring.len())
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn code(s: &str) -> LevelAndCode {
+        LevelAndCode::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_single_and_merged() {
+        let standard = code("53394611");
+        let polygons = dissolve([standard]);
+        assert_eq!(polygons.0.len(), 1);
+        assert_eq!(polygons.0[0].exterior().len(), 4);
+        assert_eq!(polygons.0[0].bounds(), Some(standard.envelope()));
+
+        // an L-shape of mixed levels
+        let polygons = dissolve([code("53394611"), code("53394612"), code("533946211")]);
+        assert_eq!(polygons.0.len(), 1);
+        let polygon = &polygons.0[0];
+        assert_eq!(polygon.exterior().len(), 6);
+        assert!(polygon.interiors().is_empty());
+        let expected = [code("53394611"), code("53394612"), code("533946211")]
+            .iter()
+            .map(|c| c.area_m2())
+            .sum::<f64>();
+        assert!((polygons.area_m2() / expected - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_holes_and_islands() {
+        // a ring of 8 standards around 53394622, with an island of one quarter code inside
+        let ring = [
+            "53394611", "53394612", "53394613", "53394621", "53394623", "53394631", "53394632",
+            "53394633",
+        ];
+        let mut codes = ring.iter().map(|c| code(c)).collect::<Vec<_>>();
+        codes.push(code("5339462214"));
+        let components = dissolve_components(codes);
+        assert_eq!(components.len(), 2);
+        let (outer, outer_codes) = &components[0];
+        assert_eq!(outer.exterior().len(), 4);
+        assert_eq!(outer.interiors().len(), 1);
+        assert_eq!(outer.interiors()[0].len(), 4);
+        assert_eq!(outer_codes.len(), 8);
+        let (island, island_codes) = &components[1];
+        assert!(island.interiors().is_empty());
+        assert_eq!(island_codes, &[code("5339462214")]);
+        assert_eq!(island.bounds(), Some(code("5339462214").envelope()));
+    }
+
+    #[test]
+    fn test_corner_touching() {
+        // a checkerboard of four cells: no two share an edge
+        let codes = [
+            code("533946111"),
+            code("533946114"),
+            code("533946122"),
+            code("533946123"),
+        ];
+        let components = dissolve_components(codes);
+        assert_eq!(components.len(), 3);
+        assert!(components.iter().all(|(p, _)| p.exterior().len() == 4));
+        assert_eq!(components.iter().map(|(_, c)| c.len()).sum::<usize>(), 4);
+
+        // a hole touching the outline at a corner: 3×3 standards without the center and a corner
+        let codes = [
+            "53394611", "53394612", "53394613", "53394621", "53394623", "53394631", "53394632",
+        ]
+        .map(code);
+        let polygons = dissolve(codes);
+        assert_eq!(polygons.0.len(), 1);
+        assert_eq!(polygons.0[0].interiors().len(), 1);
+        assert_eq!(polygons.0[0].interiors()[0].len(), 4);
+        let area = codes.iter().map(|c| c.area_m2()).sum::<f64>();
+        assert!((polygons.area_m2() / area - 1.0).abs() < 1e-9);
+
+        assert!(dissolve(Vec::<LevelAndCode>::new()).0.is_empty());
+    }
+
+    #[test]
+    fn test_many_components() {
+        // a checkerboard of 100×100 eighth codes, and nested square frames around it
+        let (x0, y0) = code("53394611111").index_xy();
+        let at = |x: u32, y: u32| LevelAndCode::from_index_xy(Level::Eighth, x0 + x, y0 + y);
+        let mut codes = (0..100)
+            .flat_map(|y| {
+                (0..100)
+                    .filter(move |x| (x + y) % 2 == 0)
+                    .map(move |x| (x, y))
+            })
+            .map(|(x, y)| at(x + 10, y + 10).unwrap())
+            .collect::<Vec<_>>();
+        for (min, max) in [(6, 113), (2, 117)] {
+            for i in min..=max {
+                codes.extend([at(i, min), at(i, max), at(min, i), at(max, i)].map(Result::unwrap));
+            }
+        }
+        let components = dissolve_components(codes.iter().copied());
+        assert_eq!(components.len(), 5_000 + 2);
+        for (polygon, members) in &components {
+            if members.len() == 1 {
+                assert_eq!(polygon.bounds(), Some(members[0].envelope()));
+            } else {
+                // the frames are 108 and 116 codes wide
+                assert!([4 * 107, 4 * 115].contains(&members.len()));
+                assert_eq!(polygon.interiors().len(), 1);
+            }
+        }
+        let total = components.iter().map(|(_, c)| c.len()).sum::<usize>();
+        assert_eq!(total, 5_000 + 4 * 107 + 4 * 115);
+    }
+}
@@ -0,0 +1,227 @@
+// GRS80 ellipsoid (JGD2011)
+
+/// Semi-major axis in meters
+pub(crate) const A: f64 = 6_378_137.0;
+/// Flattening
+pub(crate) const F: f64 = 1.0 / 298.257_222_101;
+/// First eccentricity squared
+pub(crate) const E2: f64 = F * (2.0 - F);
+
+/// Three-point Gauss–Legendre nodes on [0, 1] and their weights
+const NODES: [(f64, f64); 3] = [
+    (0.5, 4.0 / 9.0),
+    (0.5 - 0.387_298_334_620_741_7, 5.0 / 18.0),
+    (0.5 + 0.387_298_334_620_741_7, 5.0 / 18.0),
+];
+
+/// Radius of curvature in the meridian at latitude `phi` (radians)
+#[inline]
+pub(crate) fn meridian_radius(phi: f64) -> f64 {
+    let s = phi.sin();
+    A * (1.0 - E2) / (1.0 - E2 * s * s).powf(1.5)
+}
+
+/// Radius of curvature in the prime vertical at latitude `phi` (radians)
+#[inline]
+pub(crate) fn prime_vertical_radius(phi: f64) -> f64 {
+    let s = phi.sin();
+    A / (1.0 - E2 * s * s).sqrt()
+}
+
+/// Approximate length in meters of a short segment given in degrees, using the radii at its mid-latitude
+#[inline]
+pub(crate) fn segment_length(lng0: f64, lat0: f64, lng1: f64, lat1: f64) -> f64 {
+    let phi = ((lat0 + lat1) / 2.0).to_radians();
+    let dx = (lng1 - lng0).to_radians() * prime_vertical_radius(phi) * phi.cos();
+    let dy = (lat1 - lat0).to_radians() * meridian_radius(phi);
+    dx.hypot(dy)
+}
+
+/// Length in meters of the meridian arc between two latitudes (radians)
+pub(crate) fn meridian_arc(phi0: f64, phi1: f64) -> f64 {
+    let mean: f64 = NODES
+        .iter()
+        .map(|(t, w)| w * meridian_radius(phi0 + (phi1 - phi0) * t))
+        .sum();
+    (phi1 - phi0).abs() * mean
+}
+
+/// Length in meters of the arc of a parallel at latitude `phi` spanning `dlambda` (radians)
+#[inline]
+pub(crate) fn parallel_arc(phi: f64, dlambda: f64) -> f64 {
+    dlambda.abs() * prime_vertical_radius(phi) * phi.cos()
+}
+
+/// Area in m² between the equator and latitude `phi` (radians), per radian of longitude
+#[inline]
+pub(crate) fn zone_area(phi: f64) -> f64 {
+    let e = E2.sqrt();
+    let s = phi.sin();
+    A * A
+        * (1.0 - E2)
+        * (s / (2.0 * (1.0 - E2 * s * s)) + ((1.0 + e * s) / (1.0 - e * s)).ln() / (4.0 * e))
+}
+
+/// Area in m² of a box given in degrees
+#[inline]
+pub(crate) fn box_area(min_lng: f64, min_lat: f64, max_lng: f64, max_lat: f64) -> f64 {
+    (max_lng - min_lng).to_radians()
+        * (zone_area(max_lat.to_radians()) - zone_area(min_lat.to_radians()))
+}
+
+/// Area in m² of a ring given in degrees, whose edges are straight lines in (lng, lat)
+///
+/// Integrates the zone area along each edge (Green's theorem) with Gauss–Legendre quadrature.
+pub(crate) fn ring_area(ring: &[(f64, f64)]) -> f64 {
+    let Some(&last) = ring.last() else {
+        return 0.0;
+    };
+    // subtracting a constant keeps the terms small without changing the closed integral
+    let reference = zone_area(last.1.to_radians());
+    let mut prev = last;
+    let mut sum = 0.0;
+    for &cur in ring {
+        let dlng = (cur.0 - prev.0).to_radians();
+        if dlng != 0.0 {
+            let mean: f64 = NODES
+                .iter()
+                .map(|(t, w)| {
+                    let lat = prev.1 + (cur.1 - prev.1) * t;
+                    w * (zone_area(lat.to_radians()) - reference)
+                })
+                .sum();
+            sum += dlng * mean;
+        }
+        prev = cur;
+    }
+    sum.abs()
+}
+
+/// Geodesic distance in meters between two points given in degrees (Vincenty's inverse formula)
+pub(crate) fn distance(lng0: f64, lat0: f64, lng1: f64, lat1: f64) -> f64 {
+    let b = A * (1.0 - F);
+    let l = (lng1 - lng0).to_radians();
+    let u1 = ((1.0 - F) * lat0.to_radians().tan()).atan();
+    let u2 = ((1.0 - F) * lat1.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    let (mut sin_sigma, mut cos_sigma, mut sigma, mut cos2_alpha, mut cos_2sm);
+    let mut iterations = 0;
+    loop {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == 0.0 {
+            return 0.0;
+        }
+        cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+        cos_2sm = if cos2_alpha != 0.0 {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha
+        } else {
+            0.0
+        };
+        let c = F / 16.0 * cos2_alpha * (4.0 + F * (4.0 - 3.0 * cos2_alpha));
+        let prev = lambda;
+        lambda = l
+            + (1.0 - c)
+                * F
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma * (cos_2sm + c * cos_sigma * (-1.0 + 2.0 * cos_2sm * cos_2sm)));
+        iterations += 1;
+        if (lambda - prev).abs() < 1e-12 || iterations >= 200 {
+            break;
+        }
+    }
+    let u_sq = cos2_alpha * (A * A - b * b) / (b * b);
+    let aa = 1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+    let bb = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+    let delta_sigma = bb
+        * sin_sigma
+        * (cos_2sm
+            + bb / 4.0
+                * (cos_sigma * (-1.0 + 2.0 * cos_2sm * cos_2sm)
+                    - bb / 6.0
+                        * cos_2sm
+                        * (-3.0 + 4.0 * sin_sigma * sin_sigma)
+                        * (-3.0 + 4.0 * cos_2sm * cos_2sm)));
+    b * aa * (sigma - delta_sigma)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_radii() {
+        assert!((meridian_radius(0.0) - 6_335_439.327).abs() < 1e-3);
+        assert!((prime_vertical_radius(0.0) - A).abs() < 1e-9);
+        // one degree of latitude at 35°N is about 110.95 km
+        let d = segment_length(139.0, 34.5, 139.0, 35.5);
+        assert!((d - 110_950.0).abs() < 50.0, "{d}");
+    }
+
+    #[test]
+    fn test_arcs() {
+        // the quarter meridian of GRS80
+        let quarter = meridian_arc(0.0, std::f64::consts::FRAC_PI_2);
+        assert!((quarter - 10_001_965.729).abs() < 50.0, "{quarter}");
+        let d = meridian_arc(35f64.to_radians(), 36f64.to_radians());
+        assert!((d - segment_length(139.0, 35.0, 139.0, 36.0)).abs() < 0.1);
+        assert!((parallel_arc(0.0, 1f64.to_radians()) - 111_319.49).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_area() {
+        // surface area of the GRS80 ellipsoid
+        let total = 4.0 * std::f64::consts::PI * zone_area(std::f64::consts::FRAC_PI_2);
+        assert!(
+            (total / 510_065_621_718_491.0 - 1.0).abs() < 1e-9,
+            "{total}"
+        );
+
+        let rect = box_area(139.0, 35.0, 139.0125, 35.00833333);
+        let ring = ring_area(&[
+            (139.0, 35.0),
+            (139.0125, 35.0),
+            (139.0125, 35.00833333),
+            (139.0, 35.00833333),
+        ]);
+        assert!((rect - ring).abs() < 1e-6);
+        // a standard code at 35°N is about 1.14 km x 0.92 km
+        assert!((rect - 1_055_000.0).abs() < 2_000.0, "{rect}");
+
+        // the two triangles on either side of a diagonal make up the box
+        let triangle = ring_area(&[(139.0, 35.0), (140.0, 35.0), (140.0, 36.0)]);
+        let other = ring_area(&[(139.0, 35.0), (140.0, 36.0), (139.0, 36.0)]);
+        let rect = box_area(139.0, 35.0, 140.0, 36.0);
+        assert!(((triangle + other) / rect - 1.0).abs() < 1e-9);
+        // splitting the diagonal into short edges does not change the area
+        let mut ring = vec![(140.0, 35.0), (140.0, 36.0)];
+        ring.extend((0..=1000).rev().map(|i| {
+            let t = i as f64 / 1000.0;
+            (139.0 + t, 35.0 + t)
+        }));
+        assert!((ring_area(&ring) / triangle - 1.0).abs() < 1e-9);
+        // a degree square shrinks towards the pole, so the southern triangle is the larger
+        assert!(triangle > rect / 2.0);
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance(139.0, 35.0, 139.0, 35.0), 0.0);
+        // Tokyo Station to Shin-Osaka Station (the integrated geodesic is 402,476.556 m)
+        let d = distance(139.767125, 35.681236, 135.500169, 34.733412);
+        assert!((d - 402_476.556).abs() < 1e-3, "{d}");
+        // agrees with the local approximation over short distances
+        let d = distance(139.70, 35.68, 139.71, 35.69);
+        let approx = segment_length(139.70, 35.68, 139.71, 35.69);
+        assert!((d - approx).abs() < 0.01, "{d} {approx}");
+    }
+}
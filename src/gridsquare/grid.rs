@@ -0,0 +1,321 @@
+// Dense rasters of values over codes of a single level
+
+use super::iterator::index_range;
+use super::resample::{Cell, resample_cells};
+use super::*;
+use crate::Error;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+/// A dense 2D array of values for the codes of level `L` in a rectangular range
+///
+/// Values are stored row by row from the south-west corner, in the order of [`codes_in_box`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshGrid<L, T> {
+    x0: u32,
+    y0: u32,
+    width: u32,
+    height: u32,
+    values: Vec<T>,
+    _level: PhantomData<L>,
+}
+
+impl<L: MeshLevel, T> MeshGrid<L, T> {
+    /// Creates a grid over the codes intersecting the box, filled with `value`
+    pub fn new(bounds: LngLatBox, value: T) -> Self
+    where
+        T: Clone,
+    {
+        let (x0, y0, width, height) = index_range::<L>(&bounds);
+        Self {
+            x0,
+            y0,
+            width,
+            height,
+            values: vec![value; width as usize * height as usize],
+            _level: PhantomData,
+        }
+    }
+
+    /// Creates a grid over the codes intersecting the box, with values computed from the codes
+    pub fn from_fn(bounds: LngLatBox, f: impl FnMut(L) -> T) -> Self {
+        let (x0, y0, width, height) = index_range::<L>(&bounds);
+        Self {
+            x0,
+            y0,
+            width,
+            height,
+            values: codes_in_box::<L>(bounds).map(f).collect(),
+            _level: PhantomData,
+        }
+    }
+
+    /// Returns the number of columns
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the number of rows
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the union of the envelopes of the codes in the grid
+    pub fn bounds(&self) -> Option<LngLatBox> {
+        let first = self.code_at(0)?.envelope();
+        let last = self.code_at(self.values.len() - 1)?.envelope();
+        Some(first.union(&last))
+    }
+
+    /// Returns the values row by row from the south-west corner
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn get(&self, code: L) -> Option<&T> {
+        self.index_of(code).map(|i| &self.values[i])
+    }
+
+    pub fn get_mut(&mut self, code: L) -> Option<&mut T> {
+        self.index_of(code).map(|i| &mut self.values[i])
+    }
+
+    /// Sets the value of a code, failing if the code is outside the grid
+    pub fn set(&mut self, code: L, value: T) -> Result<(), Error> {
+        *self.get_mut(code).ok_or(Error::OutOfBounds)? = value;
+        Ok(())
+    }
+
+    /// Returns the codes with their values, row by row from the south-west corner
+    pub fn iter(&self) -> impl Iterator<Item = (L, &T)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (self.code_at(i).expect("index is within the grid"), v))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (L, &mut T)> + '_ {
+        let (x0, y0, width) = (self.x0, self.y0, self.width as usize);
+        self.values.iter_mut().enumerate().map(move |(i, v)| {
+            let code = L::from_index_xy(x0 + (i % width) as u32, y0 + (i / width) as u32);
+            (code.expect("index is within the grid"), v)
+        })
+    }
+
+    /// Applies a function to every value
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> MeshGrid<L, U> {
+        self.with_values(self.values.iter().map(f).collect())
+    }
+
+    /// Combines the values of two grids cell by cell
+    ///
+    /// Panics if the grids do not cover the same codes.
+    pub fn zip<U, V>(
+        &self,
+        other: &MeshGrid<L, U>,
+        mut f: impl FnMut(&T, &U) -> V,
+    ) -> MeshGrid<L, V> {
+        assert_eq!(
+            (self.x0, self.y0, self.width, self.height),
+            (other.x0, other.y0, other.width, other.height),
+            "grids must cover the same codes"
+        );
+        let values = self.values.iter().zip(&other.values).map(|(a, b)| f(a, b));
+        self.with_values(values.collect())
+    }
+
+    /// Computes a value for every cell from the values within `radius` cells of it
+    ///
+    /// The neighborhood is a square of up to `(2 × radius + 1)²` cells, clipped at the edges of
+    /// the grid, passed row by row from the south-west.
+    pub fn focal<U>(&self, radius: u32, mut f: impl FnMut(L, &[&T]) -> U) -> MeshGrid<L, U> {
+        let (w, h, r) = (self.width as i64, self.height as i64, radius as i64);
+        let mut window = Vec::with_capacity(((2 * r + 1) * (2 * r + 1)) as usize);
+        let mut values = Vec::with_capacity(self.values.len());
+        for y in 0..h {
+            for x in 0..w {
+                window.clear();
+                for wy in (y - r).max(0)..=(y + r).min(h - 1) {
+                    for wx in (x - r).max(0)..=(x + r).min(w - 1) {
+                        window.push(&self.values[(wy * w + wx) as usize]);
+                    }
+                }
+                let code = self
+                    .code_at((y * w + x) as usize)
+                    .expect("index is within the grid");
+                values.push(f(code, &window));
+            }
+        }
+        self.with_values(values)
+    }
+
+    /// Reduces the values of each zone, skipping cells without a zone
+    pub fn zonal<K: Ord, U>(
+        &self,
+        mut zone: impl FnMut(L) -> Option<K>,
+        mut reduce: impl FnMut(&[&T]) -> U,
+    ) -> BTreeMap<K, U> {
+        let mut groups: BTreeMap<K, Vec<&T>> = BTreeMap::new();
+        for (code, value) in self.iter() {
+            if let Some(key) = zone(code) {
+                groups.entry(key).or_default().push(value);
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(key, values)| (key, reduce(&values)))
+            .collect()
+    }
+
+    fn index_of(&self, code: L) -> Option<usize> {
+        let (x, y) = code.index_xy();
+        let (dx, dy) = (x.checked_sub(self.x0)?, y.checked_sub(self.y0)?);
+        (dx < self.width && dy < self.height)
+            .then(|| dy as usize * self.width as usize + dx as usize)
+    }
+
+    fn code_at(&self, i: usize) -> Option<L> {
+        if i >= self.values.len() {
+            return None;
+        }
+        let w = self.width as usize;
+        L::from_index_xy(self.x0 + (i % w) as u32, self.y0 + (i / w) as u32).ok()
+    }
+
+    fn with_values<U>(&self, values: Vec<U>) -> MeshGrid<L, U> {
+        MeshGrid {
+            x0: self.x0,
+            y0: self.y0,
+            width: self.width,
+            height: self.height,
+            values,
+            _level: PhantomData,
+        }
+    }
+}
+
+impl<L: MeshLevel, T: Copy + Into<f64>> MeshGrid<L, T> {
+    /// Converts the values to a grid of level `M` covering this one (see [`Resampling`])
+    ///
+    /// Target codes only partly covered by this grid, which happens when levels do not nest,
+    /// get values from the covered part.
+    pub fn resample<M: MeshLevel>(&self, method: Resampling) -> MeshGrid<M, f64> {
+        let (ds, dt) = (L::DIVISIONS as u64, M::DIVISIONS as u64);
+        let range = |start: u32, len: u32| {
+            let a = start as u64 * dt / ds;
+            let b = ((start + len) as u64 * dt).div_ceil(ds);
+            (a as u32, (b - a) as u32)
+        };
+        let (x0, width) = range(self.x0, self.width);
+        let (y0, height) = range(self.y0, self.height);
+        let w = self.width as usize;
+        let sources = self.values.iter().enumerate().map(|(i, value)| {
+            let (x, y) = (self.x0 + (i % w) as u32, self.y0 + (i / w) as u32);
+            let cell = Cell {
+                divisions: L::DIVISIONS,
+                x,
+                y,
+            };
+            (cell, (*value).into())
+        });
+        let mut values = vec![0.0; width as usize * height as usize];
+        for ((x, y), value) in resample_cells(sources, M::LEVEL, method) {
+            values[(y - y0) as usize * width as usize + (x - x0) as usize] = value;
+        }
+        MeshGrid {
+            x0,
+            y0,
+            width,
+            height,
+            values,
+            _level: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn bounds() -> LngLatBox {
+        // 4×3 standard codes across a secondary boundary
+        let sw = StandardCode::from_str("53394618").unwrap().envelope();
+        let ne = StandardCode::from_str("53394731").unwrap().envelope();
+        LngLatBox::new(sw.center(), ne.center())
+    }
+
+    #[test]
+    fn test_get_set() {
+        let mut grid = MeshGrid::<StandardCode, u32>::new(bounds(), 0);
+        assert_eq!((grid.width(), grid.height()), (4, 3));
+        assert_eq!(grid.len(), 12);
+        let code = StandardCode::from_str("53394710").unwrap();
+        assert_eq!(grid.get(code), Some(&0));
+        grid.set(code, 7).unwrap();
+        *grid
+            .get_mut(StandardCode::from_str("53394619").unwrap())
+            .unwrap() += 2;
+        assert_eq!(grid.get(code), Some(&7));
+        let outside = StandardCode::from_str("53394617").unwrap();
+        assert_eq!(grid.get(outside), None);
+        assert!(grid.set(outside, 1).is_err());
+        assert_eq!(grid.values().iter().sum::<u32>(), 9);
+
+        let codes = grid.iter().map(|(c, _)| c).collect::<Vec<_>>();
+        assert!(
+            codes
+                .iter()
+                .copied()
+                .eq(codes_in_box::<StandardCode>(bounds()))
+        );
+        for (code, value) in grid.iter_mut() {
+            *value = code.to_int() as u32 % 100;
+        }
+        assert_eq!(grid.get(code), Some(&10));
+        assert_eq!(
+            grid.bounds(),
+            Some(
+                StandardCode::from_str("53394618")
+                    .unwrap()
+                    .envelope()
+                    .union(&StandardCode::from_str("53394731").unwrap().envelope())
+            )
+        );
+    }
+
+    #[test]
+    fn test_map_algebra() {
+        let ones = MeshGrid::<StandardCode, f64>::new(bounds(), 1.0);
+        let ids = MeshGrid::<StandardCode, _>::from_fn(bounds(), |c| c.to_int());
+        let doubled = ones.map(|v| v * 2.0);
+        assert!(doubled.values().iter().all(|v| *v == 2.0));
+        let sum = ids.zip(&doubled, |a, b| *a as f64 + b);
+        assert_eq!(
+            sum.get(StandardCode::from_str("53394619").unwrap()),
+            Some(&53394621.0)
+        );
+
+        // number of cells in each 3×3 neighborhood
+        let counts = ones.focal(1, |_, window| window.len());
+        assert_eq!(counts.values(), &[4, 6, 6, 4, 6, 9, 9, 6, 4, 6, 6, 4]);
+        let sums = ones.focal(1, |_, window| window.iter().copied().sum::<f64>());
+        assert_eq!(sums.map(|v| *v as usize), counts);
+
+        // zones by secondary code
+        let zones = ids.zonal(|c| Some(c.secondary().to_string()), |values| values.len());
+        assert_eq!(zones.len(), 2);
+        assert_eq!(zones["533946"], 6);
+        assert_eq!(zones["533947"], 6);
+        let none = ids.zonal(|_| None::<u8>, |values| values.len());
+        assert!(none.is_empty());
+    }
+}
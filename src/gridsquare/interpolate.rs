@@ -0,0 +1,320 @@
+// Areal interpolation between polygons and codes
+
+use super::*;
+use std::collections::HashMap;
+
+/// How a variable behaves when an area is split or merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Variable {
+    /// Totals (population, counts): split in proportion to the overlapping areas
+    Extensive,
+    /// Densities and rates: averaged with the overlapping areas as weights
+    Intensive,
+}
+
+/// How much of a source overlaps the targets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceCoverage {
+    /// Area in m² of the source
+    pub area_m2: f64,
+    /// Area in m² of the source overlapping the targets
+    pub covered_m2: f64,
+}
+
+impl SourceCoverage {
+    /// Returns the covered fraction of the source
+    ///
+    /// Below 1, part of an extensive value was not carried to any target.
+    pub fn fraction(&self) -> f64 {
+        if self.area_m2 > 0.0 {
+            self.covered_m2 / self.area_m2
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Values interpolated to targets, with the coverage of each source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interpolation<T, C> {
+    pub values: T,
+    pub coverage: C,
+}
+
+/// Interpolates values given for polygons to the codes of level `L` they overlap
+///
+/// The result has a value for every code overlapping a polygon, and the coverage of the
+/// polygons in the order they are given. Areas are measured on the GRS80 ellipsoid as in
+/// [`polygon_overlay`].
+pub fn interpolate_to_mesh<'a, L: MeshLevel, V: Copy + Into<f64>>(
+    sources: impl IntoIterator<Item = (&'a MultiPolygon, V)>,
+    variable: Variable,
+) -> Interpolation<MeshMap<f64>, Vec<SourceCoverage>> {
+    let mut sums: HashMap<L, (f64, f64)> = HashMap::new();
+    let mut coverage = Vec::new();
+    for (polygon, value) in sources {
+        let (value, area_m2) = (value.into(), polygon.area_m2());
+        let mut covered_m2 = 0.0;
+        for cell in polygon_overlay::<L>(polygon).filter(|c| c.area_m2 > 0.0) {
+            covered_m2 += cell.area_m2;
+            let sum = sums.entry(cell.code).or_default();
+            match variable {
+                Variable::Extensive => sum.0 += value * cell.area_m2 / area_m2,
+                Variable::Intensive => {
+                    sum.0 += value * cell.area_m2;
+                    sum.1 += cell.area_m2;
+                }
+            }
+        }
+        coverage.push(SourceCoverage {
+            area_m2,
+            covered_m2,
+        });
+    }
+    Interpolation {
+        values: sums
+            .into_iter()
+            .map(|(code, sum)| {
+                let (x, y) = code.index_xy();
+                let code = LevelAndCode::from_index_xy(L::LEVEL, x, y);
+                (
+                    code.expect("index is within the domain"),
+                    finish(variable, sum),
+                )
+            })
+            .collect(),
+        coverage,
+    }
+}
+
+/// Interpolates values given for codes to the polygons they overlap
+///
+/// The result has a value for each target in the order they are given (None for targets
+/// not overlapping any code), and the coverage of every code of the map. Only the codes
+/// along the edge of a target are clipped against it.
+///
+/// Codes may be of mixed levels but should not overlap: a value given for both a code and a
+/// code inside it (including the standards inside an x5 or x2 code) is counted twice.
+pub fn interpolate_to_polygons<V: Copy + Into<f64>>(
+    sources: &MeshMap<V>,
+    targets: &[MultiPolygon],
+    variable: Variable,
+) -> Interpolation<Vec<Option<f64>>, MeshMap<SourceCoverage>> {
+    let mut coverage = sources
+        .iter()
+        .map(|(code, _)| {
+            let area_m2 = code.area_m2();
+            let covered_m2 = 0.0;
+            (
+                code,
+                SourceCoverage {
+                    area_m2,
+                    covered_m2,
+                },
+            )
+        })
+        .collect::<MeshMap<_>>();
+    let mut values = Vec::with_capacity(targets.len());
+    for target in targets {
+        let mut sum: Option<(f64, f64)> = None;
+        let mut add = |code: LevelAndCode, value: &V, area: Option<f64>| {
+            let source = coverage.get_mut(code).expect("every source has a coverage");
+            // codes fully inside the target overlap it by their whole area
+            let area = area.map_or(source.area_m2, |a| a.min(source.area_m2));
+            if area <= 0.0 {
+                return;
+            }
+            source.covered_m2 += area;
+            let (value, sum) = ((*value).into(), sum.get_or_insert_default());
+            match variable {
+                Variable::Extensive => sum.0 += value * area / source.area_m2,
+                Variable::Intensive => {
+                    sum.0 += value * area;
+                    sum.1 += area;
+                }
+            }
+        };
+
+        // down the quad chain from the primaries, only where there are sources
+        let primaries = target.bounds().map(primaries_from_bounds);
+        let mut stack = primaries
+            .into_iter()
+            .flatten()
+            .map(LevelAndCode::from)
+            .collect::<Vec<_>>();
+        while let Some(code) = stack.pop() {
+            if sources.within(code).next().is_none() {
+                continue;
+            }
+            let envelope = code.envelope();
+            match target.coverage(&envelope) {
+                None => {}
+                Some(Coverage::Full) => {
+                    for (c, value) in sources.within(code) {
+                        add(c, value, None);
+                    }
+                }
+                Some(Coverage::Partial) => {
+                    if let Some(value) = sources.get(code) {
+                        add(code, value, Some(target.clipped_area_m2(&envelope)));
+                    }
+                    // integrated codes are not on the quad chain, so they are clipped here
+                    if code.level() == Level::Secondary {
+                        for (c, value) in sources.within(code) {
+                            if c.level().is_integrated() {
+                                let area = target.clipped_area_m2(&c.envelope());
+                                add(c, value, Some(area));
+                            }
+                        }
+                    }
+                    stack.extend(code.children());
+                }
+            }
+        }
+        values.push(sum.map(|sum| finish(variable, sum)));
+    }
+    Interpolation { values, coverage }
+}
+
+/// Returns the value from the sums of weighted values and of weights
+fn finish(variable: Variable, (sum, weight): (f64, f64)) -> f64 {
+    match variable {
+        Variable::Extensive => sum,
+        Variable::Intensive => sum / weight,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn code(s: &str) -> LevelAndCode {
+        LevelAndCode::from_str(s).unwrap()
+    }
+
+    fn rectangle(sw: &str, ne: &str) -> MultiPolygon {
+        let envelope = code(sw).envelope().union(&code(ne).envelope());
+        MultiPolygon::from(Polygon::new(envelope.corners().to_vec(), vec![]))
+    }
+
+    #[test]
+    fn test_polygons_to_mesh() {
+        // two standards, and the west half of the second one
+        let both = rectangle("53394611", "53394612");
+        let half = rectangle("533946121", "533946123");
+
+        let result = interpolate_to_mesh::<StandardCode, _>([(&both, 100.0)], Variable::Extensive);
+        assert_eq!(result.values.len(), 2);
+        for c in ["53394611", "53394612"] {
+            assert!((result.values.get(code(c)).unwrap() - 50.0).abs() < 1e-9);
+        }
+        assert!((result.coverage[0].fraction() - 1.0).abs() < 1e-9);
+
+        let result = interpolate_to_mesh::<StandardCode, _>(
+            [(&both, 7.0), (&half, 1.0)],
+            Variable::Intensive,
+        );
+        assert!((result.values.get(code("53394611")).unwrap() - 7.0).abs() < 1e-9);
+        assert!((result.values.get(code("53394612")).unwrap() - 5.0).abs() < 1e-6);
+        assert_eq!(result.coverage.len(), 2);
+
+        // outside the domain of the codes
+        let outside = MultiPolygon::from(Polygon::new(
+            vec![
+                LngLat::new(99.0, 30.0),
+                LngLat::new(99.5, 30.0),
+                LngLat::new(99.5, 30.5),
+            ],
+            vec![],
+        ));
+        let result = interpolate_to_mesh::<StandardCode, _>([(&outside, 1.0)], Variable::Extensive);
+        assert!(result.values.is_empty());
+        assert_eq!(result.coverage[0].covered_m2, 0.0);
+        assert!(result.coverage[0].area_m2 > 0.0);
+    }
+
+    #[test]
+    fn test_mesh_to_polygons() {
+        let map = [(code("53394611"), 100.0), (code("53394612"), 40.0)]
+            .into_iter()
+            .collect::<MeshMap<f64>>();
+        // the first standard and the west half of the second, and a polygon elsewhere
+        let targets = [
+            rectangle("53394611", "533946123"),
+            rectangle("64414278", "64414278"),
+        ];
+
+        let result = interpolate_to_polygons(&map, &targets, Variable::Extensive);
+        assert!((result.values[0].unwrap() - 120.0).abs() < 1e-6);
+        assert_eq!(result.values[1], None);
+        let coverage = result.coverage.get(code("53394612")).unwrap();
+        assert!((coverage.fraction() - 0.5).abs() < 1e-4);
+        let coverage = result.coverage.get(code("53394611")).unwrap();
+        assert!((coverage.fraction() - 1.0).abs() < 1e-9);
+
+        let result = interpolate_to_polygons(&map, &targets, Variable::Intensive);
+        assert!((result.values[0].unwrap() - 80.0).abs() < 1e-3);
+
+        // the round trip through the mesh preserves totals
+        let to_mesh =
+            interpolate_to_mesh::<HalfCode, _>([(&targets[0], 90.0)], Variable::Extensive);
+        let back = interpolate_to_polygons(&to_mesh.values, &targets, Variable::Extensive);
+        assert!((back.values[0].unwrap() - 90.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_mesh_to_polygons_mixed() {
+        // halves of a secondary, standards of another, and x5 and x2 codes
+        let secondary = SecondaryCode::from_str("533946").unwrap();
+        let mut map = secondary
+            .iter_standard()
+            .flat_map(|c| c.iter_half())
+            .map(|c| (LevelAndCode::from(c), 1.0))
+            .collect::<MeshMap<f64>>();
+        for (i, c) in SecondaryCode::from_str("533947")
+            .unwrap()
+            .iter_standard()
+            .enumerate()
+        {
+            map.insert(c, i as f64);
+        }
+        map.insert(code("5339561"), 10.0);
+        map.insert(code("533957445"), 5.0);
+
+        let triangle =
+            |e: LngLatBox| MultiPolygon::from(Polygon::new(e.corners()[..3].to_vec(), vec![]));
+        let primary = PrimaryCode::from_str("5339").unwrap().envelope();
+        let targets = [
+            triangle(secondary.envelope()),
+            triangle(code("533947").envelope().union(&code("533956").envelope())),
+            rectangle("53394600", "53395799"),
+            triangle(primary),
+        ];
+        for variable in [Variable::Extensive, Variable::Intensive] {
+            let result = interpolate_to_polygons(&map, &targets, variable);
+            for (target, value) in targets.iter().zip(&result.values) {
+                // every code clipped against every target
+                let mut sum = (0.0, 0.0);
+                for (c, v) in map.iter() {
+                    let area_m2 = c.area_m2();
+                    let area = target.clipped_area_m2(&c.envelope()).min(area_m2);
+                    sum.0 += match variable {
+                        Variable::Extensive => v * area / area_m2,
+                        Variable::Intensive => v * area,
+                    };
+                    sum.1 += area;
+                }
+                let expected = finish(variable, sum);
+                assert!((value.unwrap() - expected).abs() < 1e-6 * expected.abs().max(1.0));
+            }
+        }
+        let result = interpolate_to_polygons(&map, &targets[2..3], Variable::Extensive);
+        assert!(
+            result
+                .coverage
+                .iter()
+                .all(|(_, c)| (c.fraction() - 1.0).abs() < 1e-9)
+        );
+    }
+}
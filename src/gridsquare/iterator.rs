@@ -1,87 +1,175 @@
 use super::{constants::PRIMARIES_IN_LAND, *};
 
+/// Returns the codes of level `L` within the given primaries that intersect the boundary
+///
+/// Codes are yielded primary by primary, and in code order within each primary.
+pub fn codes<L: MeshLevel>(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy,
+) -> impl Iterator<Item = L> {
+    patches::<L>(primary_iter, boundary).map(|(code, _)| code)
+}
+
+/// Returns the codes of level `L` within the given primaries that intersect the boundary, along with their envelopes
+///
+/// Codes are yielded primary by primary, and in code order within each primary.
+pub fn patches<L: MeshLevel>(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy,
+) -> impl Iterator<Item = (L, LngLatBox)> {
+    primary_iter
+        .flat_map(move |prim| L::iter_within(prim, boundary).map(|(code, patch, _)| (code, patch)))
+}
+
 pub fn primary_patches(
     primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
+    boundary: impl Boundary + Copy,
 ) -> impl Iterator<Item = (PrimaryCode, LngLatBox)> {
-    primary_iter.filter_map(move |prim| {
-        let patch = prim.envelope();
-        boundary
-            .is_none_or(|b| b.intersects_box(&patch))
-            .then_some((prim, patch))
-    })
+    patches(primary_iter, boundary)
 }
 
 pub fn secondary_patches(
     primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
+    boundary: impl Boundary + Copy,
 ) -> impl Iterator<Item = (SecondaryCode, LngLatBox)> {
-    primary_iter.flat_map(move |prim| {
-        prim.iter_secondary().filter_map(move |sec| {
-            let patch = sec.envelope();
-            boundary
-                .is_none_or(|b| b.intersects_box(&patch))
-                .then_some((sec, patch))
-        })
-    })
+    patches(primary_iter, boundary)
 }
 
 pub fn standard_patches(
     primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
+    boundary: impl Boundary + Copy,
 ) -> impl Iterator<Item = (StandardCode, LngLatBox)> {
-    secondary_codes(primary_iter, boundary).flat_map(move |sec| {
-        sec.iter_standard().filter_map(move |std| {
-            let patch = std.envelope();
-            boundary
-                .is_none_or(|b| b.intersects_box(&patch))
-                .then_some((std, patch))
-        })
-    })
+    patches(primary_iter, boundary)
 }
 
 pub fn half_patches(
     primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
+    boundary: impl Boundary + Copy,
 ) -> impl Iterator<Item = (HalfCode, LngLatBox)> {
-    standard_codes(primary_iter, boundary).flat_map(move |std| {
-        std.iter_half().filter_map(move |half| {
-            let patch = half.envelope();
-            boundary
-                .is_none_or(|b| b.intersects_box(&patch))
-                .then_some((half, patch))
-        })
-    })
+    patches(primary_iter, boundary)
 }
 
 pub fn quarter_patches(
     primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
+    boundary: impl Boundary + Copy,
 ) -> impl Iterator<Item = (QuarterCode, LngLatBox)> {
-    half_codes(primary_iter, boundary).flat_map(move |half| {
-        half.iter_quad().filter_map(move |quarter| {
-            let patch = quarter.envelope();
-            boundary
-                .is_none_or(|b| b.intersects_box(&patch))
-                .then_some((quarter, patch))
-        })
-    })
+    patches(primary_iter, boundary)
 }
 
 pub fn eighth_patches(
     primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
+    boundary: impl Boundary + Copy,
 ) -> impl Iterator<Item = (EighthCode, LngLatBox)> {
-    quarter_codes(primary_iter, boundary).flat_map(move |quarter| {
-        quarter.iter_quad().filter_map(move |eighth| {
-            let patch = eighth.envelope();
-            boundary
-                .is_none_or(|b| b.intersects_box(&patch))
-                .then_some((eighth, patch))
+    patches(primary_iter, boundary)
+}
+
+/// Returns the codes of level `L` intersecting the box, enumerated directly from the index range
+///
+/// Codes are yielded row by row from south-west to north-east. Codes merely touching the
+/// edge of the box are included, as in [`patches`].
+pub fn codes_in_box<L: MeshLevel>(bounds: LngLatBox) -> BoxCodes<L> {
+    let (x0, y0, width, height) = index_range::<L>(&bounds);
+    BoxCodes {
+        x0,
+        y0,
+        width,
+        front: 0,
+        back: width as u64 * height as u64,
+        _level: std::marker::PhantomData,
+    }
+}
+
+/// Returns the (x, y) index of the south-west code of level `L` intersecting the box and the
+/// number of columns and rows of codes intersecting it
+pub(crate) fn index_range<L: MeshLevel>(bounds: &LngLatBox) -> (u32, u32, u32, u32) {
+    let d = L::DIVISIONS as f64;
+    let limit = 100 * L::DIVISIONS as i64 - 1;
+    // boxes outside the domain give an empty range rather than the edge row or column
+    let range = |min: f64, max: f64, offset: i64| {
+        let a = ((min * d).ceil() as i64 - 1 - offset).max(0);
+        let b = ((max * d).floor() as i64 - offset).min(limit);
+        if a > b {
+            return (0, 0);
+        }
+        (a as u32, (b + 1 - a) as u32)
+    };
+    let (x0, width) = range(
+        bounds.min.vlng / 30.,
+        bounds.max.vlng / 30.,
+        100 * L::DIVISIONS as i64,
+    );
+    let (y0, height) = range(bounds.min.vlat / 20., bounds.max.vlat / 20., 0);
+    (x0, y0, width, height)
+}
+
+/// Returns the number of codes of level `L` intersecting the box without enumerating them
+pub fn count_in_box<L: MeshLevel>(bounds: LngLatBox) -> usize {
+    codes_in_box::<L>(bounds).len()
+}
+
+/// Returns the `n`-th code of [`codes_in_box`] without enumerating the preceding codes
+pub fn nth_in_box<L: MeshLevel>(bounds: LngLatBox, n: usize) -> Option<L> {
+    codes_in_box::<L>(bounds).nth(n)
+}
+
+/// Iterator returned by [`codes_in_box`]
+#[derive(Debug, Clone)]
+pub struct BoxCodes<L> {
+    x0: u32,
+    y0: u32,
+    width: u32,
+    front: u64,
+    back: u64,
+    _level: std::marker::PhantomData<L>,
+}
+
+impl<L: MeshLevel> BoxCodes<L> {
+    #[inline]
+    fn code_at(&self, i: u64) -> L {
+        let x = self.x0 + (i % self.width as u64) as u32;
+        let y = self.y0 + (i / self.width as u64) as u32;
+        L::from_index_xy(x, y).expect("index is within the domain")
+    }
+}
+
+impl<L: MeshLevel> Iterator for BoxCodes<L> {
+    type Item = L;
+
+    #[inline]
+    fn next(&mut self) -> Option<L> {
+        (self.front < self.back).then(|| {
+            self.front += 1;
+            self.code_at(self.front - 1)
         })
-    })
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<L> {
+        self.front = self.front.saturating_add(n as u64).min(self.back);
+        self.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.back - self.front) as usize;
+        (len, Some(len))
+    }
 }
 
+impl<L: MeshLevel> DoubleEndedIterator for BoxCodes<L> {
+    #[inline]
+    fn next_back(&mut self) -> Option<L> {
+        (self.front < self.back).then(|| {
+            self.back -= 1;
+            self.code_at(self.back)
+        })
+    }
+}
+
+impl<L: MeshLevel> ExactSizeIterator for BoxCodes<L> {}
+
+impl<L: MeshLevel> std::iter::FusedIterator for BoxCodes<L> {}
+
 pub fn primaries_in_land() -> impl Iterator<Item = PrimaryCode> {
     PRIMARIES_IN_LAND.iter().cloned()
 }
@@ -99,49 +187,10 @@ pub fn primaries_from_bounds(bounds: LngLatBox) -> impl Iterator<Item = PrimaryC
     })
 }
 
-fn secondary_codes(
-    primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
-) -> impl Iterator<Item = SecondaryCode> {
-    primary_iter.flat_map(move |prim| {
-        prim.iter_secondary()
-            .filter(move |sec| boundary.is_none_or(|b| b.intersects_box(&sec.envelope())))
-    })
-}
-
-fn standard_codes(
-    primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
-) -> impl Iterator<Item = StandardCode> {
-    secondary_codes(primary_iter, boundary).flat_map(move |sec| {
-        sec.iter_standard()
-            .filter(move |std| boundary.is_none_or(|b| b.intersects_box(&std.envelope())))
-    })
-}
-
-fn half_codes(
-    primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
-) -> impl Iterator<Item = HalfCode> {
-    standard_codes(primary_iter, boundary).flat_map(move |std| {
-        std.iter_half()
-            .filter(move |half| boundary.is_none_or(|b| b.intersects_box(&half.envelope())))
-    })
-}
-
-fn quarter_codes(
-    primary_iter: impl Iterator<Item = PrimaryCode>,
-    boundary: Option<LngLatBox>,
-) -> impl Iterator<Item = QuarterCode> {
-    half_codes(primary_iter, boundary).flat_map(move |half| {
-        half.iter_quad()
-            .filter(move |quarter| boundary.is_none_or(|b| b.intersects_box(&quarter.envelope())))
-    })
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
 
     #[test]
     fn test_patches() {
@@ -179,6 +228,87 @@ mod tests {
             .inspect(|(_, e)| assert!(boundary.intersects_box(e)))
             .count();
         assert_eq!(count, 30544);
+
+        let count = codes::<EighthCode>(primaries_in_land(), Some(boundary))
+            .inspect(|c| assert!(boundary.intersects_box(&c.envelope())))
+            .count();
+        assert_eq!(count, 30544);
+
+        assert_eq!(
+            codes::<SecondaryCode>(primaries_in_land(), None).count(),
+            176 * 64
+        );
+    }
+
+    #[test]
+    fn test_patches_order() {
+        let boundary = LngLatBox::new(
+            LngLat::new(141.305438074, 42.939466350),
+            LngLat::new(141.563765511, 43.129434849),
+        );
+
+        let secondaries = secondary_patches(primaries_in_land(), Some(boundary))
+            .map(|(c, _)| c.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            secondaries,
+            [
+                "644132", "644133", "644134", "644142", "644143", "644144", "644152", "644153",
+                "644154"
+            ]
+        );
+
+        let standards = standard_patches(primaries_in_land(), Some(boundary))
+            .map(|(c, _)| c)
+            .collect::<Vec<_>>();
+        assert!(standards.is_sorted_by(|a, b| a.to_string() < b.to_string()));
+    }
+
+    #[test]
+    fn test_codes_in_box() {
+        let boundary = LngLatBox::new(
+            LngLat::new(141.305438074, 42.939466350),
+            LngLat::new(141.563765511, 43.129434849),
+        );
+        assert_eq!(count_in_box::<PrimaryCode>(boundary), 1);
+        assert_eq!(count_in_box::<SecondaryCode>(boundary), 9);
+        assert_eq!(count_in_box::<StandardCode>(boundary), 528);
+        assert_eq!(count_in_box::<HalfCode>(boundary), 2021);
+        assert_eq!(count_in_box::<QuarterCode>(boundary), 7812);
+        assert_eq!(count_in_box::<EighthCode>(boundary), 30544);
+
+        let mut expected = codes::<EighthCode>(primaries_in_land(), boundary).collect::<Vec<_>>();
+        expected.sort();
+        let mut actual = codes_in_box::<EighthCode>(boundary).collect::<Vec<_>>();
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        let iter = codes_in_box::<HalfCode>(boundary);
+        let all = iter.clone().collect::<Vec<_>>();
+        assert_eq!(nth_in_box::<HalfCode>(boundary, 1234), Some(all[1234]));
+        assert_eq!(nth_in_box::<HalfCode>(boundary, 2021), None);
+        assert_eq!(iter.clone().next_back(), all.last().copied());
+
+        // touching the edge of a cell
+        let code = StandardCode::from_str("64414278").unwrap();
+        assert_eq!(count_in_box::<StandardCode>(code.envelope()), 9);
+
+        // outside the domain
+        let outside = LngLatBox::new(LngLat::new(10.0, -10.0), LngLat::new(20.0, -5.0));
+        assert_eq!(count_in_box::<StandardCode>(outside), 0);
+        // entirely east, west, north or south of the domain, within the other axis
+        for (min, max) in [
+            (LngLat::new(210.0, 35.0), LngLat::new(220.0, 36.0)),
+            (LngLat::new(90.0, 35.0), LngLat::new(95.0, 36.0)),
+            (LngLat::new(140.0, 70.0), LngLat::new(141.0, 71.0)),
+            (LngLat::new(140.0, -5.0), LngLat::new(141.0, -1.0)),
+        ] {
+            let outside = LngLatBox::new(min, max);
+            assert_eq!(count_in_box::<PrimaryCode>(outside), 0);
+            assert_eq!(count_in_box::<StandardCode>(outside), 0);
+            assert_eq!(nth_in_box::<StandardCode>(outside, 0), None);
+            assert_eq!(codes_in_box::<EighthCode>(outside).next(), None);
+        }
     }
 
     #[test]
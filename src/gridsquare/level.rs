@@ -0,0 +1,640 @@
+// Level hierarchy (primary → secondary → standard → half → quarter → eighth, with the
+// integrated x5 and x2 levels dividing secondary codes)
+
+use super::constants::PRIMARIES_IN_LAND;
+use super::*;
+use crate::Error;
+
+/// Levels of the hierarchy, from coarsest to finest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Primary,
+    Secondary,
+    /// 5-times mesh: secondary codes divided into 2×2
+    X5,
+    /// 2-times mesh: secondary codes divided into 5×5
+    X2,
+    Standard,
+    Half,
+    Quarter,
+    Eighth,
+}
+
+impl Level {
+    /// Levels of the quad chain, where each level subdivides the previous one
+    pub const ALL: [Level; 6] = [
+        Level::Primary,
+        Level::Secondary,
+        Level::Standard,
+        Level::Half,
+        Level::Quarter,
+        Level::Eighth,
+    ];
+
+    /// Integrated levels, which subdivide secondary codes outside the quad chain
+    pub const INTEGRATED: [Level; 2] = [Level::X5, Level::X2];
+
+    /// Returns true for the integrated levels (see [`Level::INTEGRATED`])
+    pub const fn is_integrated(self) -> bool {
+        matches!(self, Level::X5 | Level::X2)
+    }
+
+    /// Number of cells along each axis of a primary code
+    pub const fn divisions(self) -> u32 {
+        match self {
+            Level::Primary => 1,
+            Level::Secondary => 8,
+            Level::X5 => 16,
+            Level::X2 => 40,
+            Level::Standard => 80,
+            Level::Half => 160,
+            Level::Quarter => 320,
+            Level::Eighth => 640,
+        }
+    }
+
+    /// Number of cells along each axis of a parent code
+    pub const fn split(self) -> u32 {
+        match self {
+            Level::Primary => 1,
+            Level::Secondary => 8,
+            Level::X5 => 2,
+            Level::X2 => 5,
+            Level::Standard => 10,
+            Level::Half | Level::Quarter | Level::Eighth => 2,
+        }
+    }
+
+    /// Number of digits of the codes
+    pub const fn digits(self) -> u32 {
+        match self {
+            Level::Primary => 4,
+            Level::Secondary => 6,
+            Level::X5 => 7,
+            Level::X2 => 9,
+            Level::Standard => 8,
+            Level::Half => 9,
+            Level::Quarter => 10,
+            Level::Eighth => 11,
+        }
+    }
+
+    pub const fn parent(self) -> Option<Level> {
+        match self {
+            Level::Primary => None,
+            Level::Secondary => Some(Level::Primary),
+            Level::X5 | Level::X2 | Level::Standard => Some(Level::Secondary),
+            Level::Half => Some(Level::Standard),
+            Level::Quarter => Some(Level::Half),
+            Level::Eighth => Some(Level::Quarter),
+        }
+    }
+
+    /// Returns the next finer level of the quad chain (None for the integrated levels)
+    pub const fn child(self) -> Option<Level> {
+        match self {
+            Level::Primary => Some(Level::Secondary),
+            Level::Secondary => Some(Level::Standard),
+            Level::X5 | Level::X2 => None,
+            Level::Standard => Some(Level::Half),
+            Level::Half => Some(Level::Quarter),
+            Level::Quarter => Some(Level::Eighth),
+            Level::Eighth => None,
+        }
+    }
+}
+
+/// Dimensions of a cell in meters on the GRS80 ellipsoid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellSize {
+    pub width_m: f64,
+    pub height_m: f64,
+    pub area_m2: f64,
+}
+
+/// Returns the smallest and largest dimensions of cells at `level` over the primaries in land
+///
+/// Width and area shrink and height grows toward the pole, so the extremes are found at the
+/// southernmost and northernmost rows.
+fn cell_size_range<L: MeshLevel>() -> (CellSize, CellSize) {
+    let ys = PRIMARIES_IN_LAND.iter().map(|p| p.y as u32);
+    let (south, north) = (ys.clone().min().unwrap(), ys.max().unwrap());
+    let x = PRIMARIES_IN_LAND[0].x as u32 * L::DIVISIONS;
+    let size = |code: L| CellSize {
+        width_m: code.width_m(),
+        height_m: code.height_m(),
+        area_m2: code.area_m2(),
+    };
+    let a = size(L::from_index_xy(x, south * L::DIVISIONS).unwrap());
+    let b = size(L::from_index_xy(x, (north + 1) * L::DIVISIONS - 1).unwrap());
+    (
+        CellSize {
+            width_m: a.width_m.min(b.width_m),
+            height_m: a.height_m.min(b.height_m),
+            area_m2: a.area_m2.min(b.area_m2),
+        },
+        CellSize {
+            width_m: a.width_m.max(b.width_m),
+            height_m: a.height_m.max(b.height_m),
+            area_m2: a.area_m2.max(b.area_m2),
+        },
+    )
+}
+
+/// A level of the hierarchy reached by repeatedly subdividing primary codes
+///
+/// Generic iteration, lookup by index and neighbor search are built on this trait,
+/// so every level implementing it gets them for free.
+pub trait MeshLevel: GridSquareCode + Copy + Eq + Ord + std::hash::Hash + std::fmt::Debug {
+    /// The next coarser level (the primary level is its own parent)
+    type Parent: MeshLevel;
+
+    const LEVEL: Level;
+
+    /// Number of cells along each axis of a parent code
+    const SPLIT: u32;
+
+    /// Number of cells along each axis of a primary code
+    const DIVISIONS: u32 = Self::SPLIT * <Self::Parent as MeshLevel>::DIVISIONS;
+
+    /// Returns the parent code
+    fn parent(&self) -> Self::Parent;
+
+    /// Returns the child of `parent` at the offset (dx, dy), each in `0..SPLIT`
+    fn from_parent(parent: Self::Parent, dx: u32, dy: u32) -> Self;
+
+    /// Returns the (dx, dy) offset of this code within its parent
+    fn offset(&self) -> (u32, u32);
+
+    fn from_lnglat(lnglat: LngLat) -> Result<Self, Error>;
+
+    /// Returns the code as an integer
+    fn to_int(&self) -> u64;
+
+    /// Inverse of [`GridSquareCode::index_xy`]
+    fn from_index_xy(x: u32, y: u32) -> Result<Self, Error> {
+        let parent = Self::Parent::from_index_xy(x / Self::SPLIT, y / Self::SPLIT)?;
+        Ok(Self::from_parent(parent, x % Self::SPLIT, y % Self::SPLIT))
+    }
+
+    /// Returns the position of this code among all codes of this level in code order
+    ///
+    /// Ordinals are dense (every value below `10000 × DIVISIONS²` is a code) and fit in
+    /// `u32` even at the eighth level.
+    fn ordinal(&self) -> u32 {
+        let (dx, dy) = self.offset();
+        (self.parent().ordinal() * Self::SPLIT + dy) * Self::SPLIT + dx
+    }
+
+    /// Inverse of [`MeshLevel::ordinal`]
+    fn from_ordinal(ordinal: u32) -> Result<Self, Error> {
+        let n = Self::SPLIT * Self::SPLIT;
+        let parent = Self::Parent::from_ordinal(ordinal / n)?;
+        let r = ordinal % n;
+        Ok(Self::from_parent(parent, r % Self::SPLIT, r / Self::SPLIT))
+    }
+
+    /// Returns the code `dx` cells east and `dy` cells north of this one, crossing parent
+    /// boundaries, or None if it is outside the domain
+    fn neighbor(&self, dx: i32, dy: i32) -> Option<Self> {
+        let (x, y) = self.index_xy();
+        let x = x.checked_add_signed(dx)?;
+        let y = y.checked_add_signed(dy)?;
+        Self::from_index_xy(x, y).ok()
+    }
+
+    /// Returns the adjacent codes: east, north, west and south, followed by the diagonal ones
+    /// for 8-connectivity
+    fn neighbors(&self, connectivity: Connectivity) -> impl Iterator<Item = Self> {
+        let code = *self;
+        connectivity
+            .offsets()
+            .iter()
+            .filter_map(move |(dx, dy)| code.neighbor(*dx, *dy))
+    }
+
+    /// Returns the smallest width, height and area of cells of this level in Japan
+    fn min_cell_size() -> CellSize {
+        cell_size_range::<Self>().0
+    }
+
+    /// Returns the largest width, height and area of cells of this level in Japan
+    fn max_cell_size() -> CellSize {
+        cell_size_range::<Self>().1
+    }
+
+    /// Returns the primary code containing this code
+    fn primary(&self) -> PrimaryCode {
+        self.parent().primary()
+    }
+
+    /// Returns an iterator over the children of `parent` in code order
+    fn iter_children_of(parent: Self::Parent) -> impl Iterator<Item = Self> {
+        (0..Self::SPLIT)
+            .flat_map(move |dy| (0..Self::SPLIT).map(move |dx| Self::from_parent(parent, dx, dy)))
+    }
+
+    /// Returns the codes within `primary` that intersect the boundary, along with their envelopes
+    /// and how much of each is covered
+    ///
+    /// Children of a patch outside the boundary are never visited, and children of a fully
+    /// covered patch are yielded without being tested.
+    fn iter_within<B: Boundary + Copy>(
+        primary: PrimaryCode,
+        boundary: B,
+    ) -> impl Iterator<Item = (Self, LngLatBox, Coverage)> {
+        Self::Parent::iter_within(primary, boundary).flat_map(move |(parent, _, coverage)| {
+            Self::iter_children_of(parent).filter_map(move |code| {
+                let patch = code.envelope();
+                match coverage {
+                    Coverage::Full => Some((code, patch, Coverage::Full)),
+                    Coverage::Partial => boundary.coverage(&patch).map(|c| (code, patch, c)),
+                }
+            })
+        })
+    }
+}
+
+impl MeshLevel for PrimaryCode {
+    type Parent = PrimaryCode;
+    const LEVEL: Level = Level::Primary;
+    const SPLIT: u32 = 1;
+    const DIVISIONS: u32 = 1;
+
+    #[inline]
+    fn parent(&self) -> Self::Parent {
+        *self
+    }
+
+    #[inline]
+    fn from_parent(parent: Self::Parent, _dx: u32, _dy: u32) -> Self {
+        parent
+    }
+
+    #[inline]
+    fn offset(&self) -> (u32, u32) {
+        (0, 0)
+    }
+
+    #[inline]
+    fn from_lnglat(lnglat: LngLat) -> Result<Self, Error> {
+        PrimaryCode::from_lnglat(lnglat)
+    }
+
+    #[inline]
+    fn to_int(&self) -> u64 {
+        self.y as u64 * 100 + self.x as u64
+    }
+
+    #[inline]
+    fn from_index_xy(x: u32, y: u32) -> Result<Self, Error> {
+        if x > 99 || y > 99 {
+            return Err(Error::OutOfBounds);
+        }
+        Ok(Self {
+            y: y as u8,
+            x: x as u8,
+        })
+    }
+
+    #[inline]
+    fn ordinal(&self) -> u32 {
+        self.y as u32 * 100 + self.x as u32
+    }
+
+    #[inline]
+    fn from_ordinal(ordinal: u32) -> Result<Self, Error> {
+        if ordinal >= 10000 {
+            return Err(Error::OutOfBounds);
+        }
+        Ok(Self {
+            y: (ordinal / 100) as u8,
+            x: (ordinal % 100) as u8,
+        })
+    }
+
+    #[inline]
+    fn primary(&self) -> PrimaryCode {
+        *self
+    }
+
+    fn iter_within<B: Boundary + Copy>(
+        primary: PrimaryCode,
+        boundary: B,
+    ) -> impl Iterator<Item = (Self, LngLatBox, Coverage)> {
+        let patch = primary.envelope();
+        boundary
+            .coverage(&patch)
+            .map(|c| (primary, patch, c))
+            .into_iter()
+    }
+}
+
+impl MeshLevel for SecondaryCode {
+    type Parent = PrimaryCode;
+    const LEVEL: Level = Level::Secondary;
+    const SPLIT: u32 = 8;
+
+    #[inline]
+    fn parent(&self) -> Self::Parent {
+        self.primary
+    }
+
+    #[inline]
+    fn from_parent(parent: Self::Parent, dx: u32, dy: u32) -> Self {
+        Self {
+            primary: parent,
+            y2: dy as u8,
+            x2: dx as u8,
+        }
+    }
+
+    #[inline]
+    fn offset(&self) -> (u32, u32) {
+        (self.x2 as u32, self.y2 as u32)
+    }
+
+    #[inline]
+    fn from_lnglat(lnglat: LngLat) -> Result<Self, Error> {
+        SecondaryCode::from_lnglat(lnglat)
+    }
+
+    #[inline]
+    fn to_int(&self) -> u64 {
+        self.primary.to_int() * 100 + self.y2 as u64 * 10 + self.x2 as u64
+    }
+}
+
+impl MeshLevel for StandardCode {
+    type Parent = SecondaryCode;
+    const LEVEL: Level = Level::Standard;
+    const SPLIT: u32 = 10;
+
+    #[inline]
+    fn parent(&self) -> Self::Parent {
+        self.secondary
+    }
+
+    #[inline]
+    fn from_parent(parent: Self::Parent, dx: u32, dy: u32) -> Self {
+        Self {
+            secondary: parent,
+            y3: dy as u8,
+            x3: dx as u8,
+        }
+    }
+
+    #[inline]
+    fn offset(&self) -> (u32, u32) {
+        (self.x3 as u32, self.y3 as u32)
+    }
+
+    #[inline]
+    fn from_lnglat(lnglat: LngLat) -> Result<Self, Error> {
+        StandardCode::from_lnglat(lnglat)
+    }
+
+    #[inline]
+    fn to_int(&self) -> u64 {
+        self.secondary.to_int() * 100 + self.y3 as u64 * 10 + self.x3 as u64
+    }
+}
+
+impl MeshLevel for X5Code {
+    type Parent = SecondaryCode;
+    const LEVEL: Level = Level::X5;
+    const SPLIT: u32 = 2;
+
+    #[inline]
+    fn parent(&self) -> Self::Parent {
+        self.secondary
+    }
+
+    #[inline]
+    fn from_parent(parent: Self::Parent, dx: u32, dy: u32) -> Self {
+        Self {
+            secondary: parent,
+            quad: (dy * 2 + dx + 1) as u8,
+        }
+    }
+
+    #[inline]
+    fn offset(&self) -> (u32, u32) {
+        let d = (self.quad - 1) as u32;
+        (d & 1, d >> 1)
+    }
+
+    #[inline]
+    fn from_lnglat(lnglat: LngLat) -> Result<Self, Error> {
+        X5Code::from_lnglat(lnglat)
+    }
+
+    #[inline]
+    fn to_int(&self) -> u64 {
+        self.secondary.to_int() * 10 + self.quad as u64
+    }
+}
+
+impl MeshLevel for X2Code {
+    type Parent = SecondaryCode;
+    const LEVEL: Level = Level::X2;
+    const SPLIT: u32 = 5;
+
+    #[inline]
+    fn parent(&self) -> Self::Parent {
+        self.secondary
+    }
+
+    #[inline]
+    fn from_parent(parent: Self::Parent, dx: u32, dy: u32) -> Self {
+        Self {
+            secondary: parent,
+            y: dy as u8,
+            x: dx as u8,
+        }
+    }
+
+    #[inline]
+    fn offset(&self) -> (u32, u32) {
+        (self.x as u32, self.y as u32)
+    }
+
+    #[inline]
+    fn from_lnglat(lnglat: LngLat) -> Result<Self, Error> {
+        X2Code::from_lnglat(lnglat)
+    }
+
+    #[inline]
+    fn to_int(&self) -> u64 {
+        self.secondary.to_int() * 1000 + self.y as u64 * 200 + self.x as u64 * 20 + 5
+    }
+}
+
+impl<P: MeshLevel> MeshLevel for Quad<P> {
+    type Parent = P;
+    const LEVEL: Level = match P::LEVEL.child() {
+        Some(level) => level,
+        None => panic!("no level below the eighth level"),
+    };
+    const SPLIT: u32 = 2;
+
+    #[inline]
+    fn parent(&self) -> Self::Parent {
+        self.parent
+    }
+
+    #[inline]
+    fn from_parent(parent: Self::Parent, dx: u32, dy: u32) -> Self {
+        Self {
+            parent,
+            quad: (dy * 2 + dx + 1) as u8,
+        }
+    }
+
+    #[inline]
+    fn offset(&self) -> (u32, u32) {
+        let d = (self.quad - 1) as u32;
+        (d & 1, d >> 1)
+    }
+
+    #[inline]
+    fn from_lnglat(lnglat: LngLat) -> Result<Self, Error> {
+        let yd = (lnglat.vlat / 20. * Self::DIVISIONS as f64) as u64 % 2;
+        let xd = (lnglat.vlng / 30. * Self::DIVISIONS as f64) as u64 % 2;
+        Ok(Self::from_parent(
+            P::from_lnglat(lnglat)?,
+            xd as u32,
+            yd as u32,
+        ))
+    }
+
+    #[inline]
+    fn to_int(&self) -> u64 {
+        self.parent.to_int() * 10 + self.quad as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_divisions() {
+        assert_eq!(PrimaryCode::DIVISIONS, 1);
+        assert_eq!(SecondaryCode::DIVISIONS, 8);
+        assert_eq!(StandardCode::DIVISIONS, 80);
+        assert_eq!(HalfCode::DIVISIONS, 160);
+        assert_eq!(QuarterCode::DIVISIONS, 320);
+        assert_eq!(EighthCode::DIVISIONS, 640);
+
+        assert_eq!(PrimaryCode::LEVEL, Level::Primary);
+        assert_eq!(QuarterCode::LEVEL, Level::Quarter);
+        assert_eq!(EighthCode::LEVEL, Level::Eighth);
+        for level in Level::ALL {
+            let parent = level.parent().map_or(1, |p| p.divisions());
+            assert_eq!(level.divisions(), parent * level.split());
+            assert_eq!(
+                level.parent().and_then(|p| p.child()).unwrap_or(level),
+                level
+            );
+        }
+        assert_eq!(EighthCode::DIVISIONS, Level::Eighth.divisions());
+
+        assert_eq!(X5Code::DIVISIONS, Level::X5.divisions());
+        assert_eq!(X2Code::DIVISIONS, Level::X2.divisions());
+        for level in Level::INTEGRATED {
+            assert_eq!(level.divisions(), 8 * level.split());
+            assert_eq!(level.child(), None);
+        }
+    }
+
+    #[test]
+    fn test_ordinal() {
+        fn check<L: MeshLevel>(code: &str) {
+            let code = LevelAndCode::from_str(code).unwrap();
+            let (x, y) = code.index_xy();
+            let code = L::from_index_xy(x, y).unwrap();
+            let ordinal = code.ordinal();
+            assert_eq!(L::from_ordinal(ordinal).unwrap(), code);
+            // the ordinal follows code order
+            let next = L::from_ordinal(ordinal + 1).unwrap();
+            assert!(code < next);
+            assert!(L::from_ordinal(10000 * L::DIVISIONS * L::DIVISIONS).is_err());
+        }
+        check::<PrimaryCode>("5339");
+        check::<SecondaryCode>("533977");
+        check::<StandardCode>("53397799");
+        check::<HalfCode>("533977994");
+        check::<QuarterCode>("5339779944");
+        check::<X5Code>("5339772");
+        check::<X2Code>("533977485");
+        check::<EighthCode>("53397799444");
+
+        let last = EighthCode::from_str("99997799444").unwrap();
+        assert_eq!(last.ordinal(), 10000 * 640 * 640 - 1);
+        assert_eq!(
+            StandardCode::from_str("53394611").unwrap().ordinal(),
+            (5339 * 64 + 4 * 8 + 6) * 100 + 11
+        );
+    }
+
+    #[test]
+    fn test_neighbors() {
+        // crossing standard, secondary and primary boundaries
+        let code = HalfCode::from_str("533977994").unwrap();
+        assert_eq!(code.neighbor(1, 0).unwrap().to_string(), "534070903");
+        assert_eq!(code.neighbor(0, 1).unwrap().to_string(), "543907092");
+        assert_eq!(code.neighbor(-1, -1).unwrap().to_string(), "533977991");
+        assert_eq!(code.neighbor(2, 2).unwrap().neighbor(-2, -2), Some(code));
+        assert_eq!(code.neighbors(Connectivity::Four).count(), 4);
+        assert_eq!(code.neighbors(Connectivity::Eight).count(), 8);
+
+        let corner = PrimaryCode::from_str("0000").unwrap();
+        assert_eq!(corner.neighbor(-1, 0), None);
+        assert_eq!(corner.neighbors(Connectivity::Eight).count(), 3);
+    }
+
+    #[test]
+    fn test_cell_size() {
+        let min = StandardCode::min_cell_size();
+        let max = StandardCode::max_cell_size();
+        // from northern Hokkaido (about 0.97 km wide) to Okinotorishima at 20°N (about 1.31 km wide)
+        assert!((min.width_m - 968.0).abs() < 10.0, "{min:?}");
+        assert!((max.width_m - 1_308.0).abs() < 10.0, "{max:?}");
+        assert!(min.height_m < max.height_m && max.height_m < 930.0);
+        assert!(min.area_m2 < max.area_m2);
+
+        let code = StandardCode::from_str("53394611").unwrap();
+        assert!(min.area_m2 < code.area_m2() && code.area_m2() < max.area_m2);
+        assert!((EighthCode::max_cell_size().area_m2 * 64.0 / max.area_m2 - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let code = EighthCode::from_str("64414278234").unwrap();
+        assert_eq!(MeshLevel::parent(&code).to_string(), "6441427823");
+        assert_eq!(code.offset(), (1, 1));
+        assert_eq!(MeshLevel::primary(&code).to_string(), "6441");
+        assert_eq!(code.to_int(), 64414278234);
+        assert_eq!(code.parent().parent().parent().to_int(), 64414278);
+        assert_eq!(
+            <EighthCode as MeshLevel>::from_lnglat(LngLat::new(141.8686372, 43.2404931)).unwrap(),
+            EighthCode::from_lnglat(LngLat::new(141.8686372, 43.2404931)).unwrap()
+        );
+
+        let (x, y) = code.index_xy();
+        assert_eq!(EighthCode::from_index_xy(x, y).unwrap(), code);
+        let code = StandardCode::from_str("64414278").unwrap();
+        let (x, y) = code.index_xy();
+        assert_eq!(StandardCode::from_index_xy(x, y).unwrap(), code);
+        StandardCode::from_index_xy(8000, 0).expect_err("out of bounds");
+
+        let children = StandardCode::iter_children_of(code.secondary()).collect::<Vec<_>>();
+        assert_eq!(children.len(), 100);
+        assert!(children.is_sorted());
+        assert_eq!(children[78], code);
+        let children = HalfCode::iter_children_of(code).collect::<Vec<_>>();
+        assert_eq!(children, code.iter_half().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,181 @@
+// Line coverage (exact grid traversal of linestrings)
+
+use super::ellipsoid::segment_length;
+use super::*;
+
+/// A cell crossed by a line, with the length of the line inside it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineCell<L> {
+    pub code: L,
+    /// Length in degrees, measured in the (lng, lat) plane
+    pub length_deg: f64,
+    /// Length in meters on the GRS80 ellipsoid
+    pub length_m: f64,
+}
+
+/// Returns the cells of level `L` crossed by the linestring, in the order the line passes through them
+///
+/// Consecutive pieces in the same cell are merged; a cell entered again later appears again.
+/// Cells merely touched at a corner or along an edge are not reported. Parts of the line
+/// outside the domain of the grid square codes are ignored.
+pub fn line_coverage<L: MeshLevel>(line: &[LngLat]) -> Vec<LineCell<L>> {
+    let mut cells: Vec<LineCell<L>> = Vec::new();
+    for segment in line.windows(2) {
+        traverse_segment::<L>(segment[0], segment[1], |code, t0, t1| {
+            let (p, q) = (segment[0], segment[1]);
+            let lng0 = p.lng() + (q.lng() - p.lng()) * t0;
+            let lat0 = p.lat() + (q.lat() - p.lat()) * t0;
+            let lng1 = p.lng() + (q.lng() - p.lng()) * t1;
+            let lat1 = p.lat() + (q.lat() - p.lat()) * t1;
+            let length_deg = (lng1 - lng0).hypot(lat1 - lat0);
+            let length_m = segment_length(lng0, lat0, lng1, lat1);
+            match cells.last_mut() {
+                Some(last) if last.code == code => {
+                    last.length_deg += length_deg;
+                    last.length_m += length_m;
+                }
+                _ => cells.push(LineCell {
+                    code,
+                    length_deg,
+                    length_m,
+                }),
+            }
+        });
+    }
+    cells
+}
+
+/// Walks the cells crossed by a segment (Amanatides–Woo), calling `f` with the parameter range inside each cell
+fn traverse_segment<L: MeshLevel>(p: LngLat, q: LngLat, mut f: impl FnMut(L, f64, f64)) {
+    let d = L::DIVISIONS as f64;
+    let to_grid = |p: LngLat| (p.vlng / 30. * d - 100. * d, p.vlat / 20. * d);
+    let (x0, y0) = to_grid(p);
+    let (x1, y1) = to_grid(q);
+    let (dx, dy) = (x1 - x0, y1 - y0);
+
+    let mut cx = x0.floor() as i64;
+    let mut cy = y0.floor() as i64;
+    let step_x: i64 = if dx > 0. { 1 } else { -1 };
+    let step_y: i64 = if dy > 0. { 1 } else { -1 };
+    let next_boundary = |c: i64, step: i64| if step > 0 { c + 1 } else { c } as f64;
+    let mut t_max_x = if dx != 0. {
+        (next_boundary(cx, step_x) - x0) / dx
+    } else {
+        f64::INFINITY
+    };
+    let mut t_max_y = if dy != 0. {
+        (next_boundary(cy, step_y) - y0) / dy
+    } else {
+        f64::INFINITY
+    };
+    let t_delta_x = (1. / dx).abs();
+    let t_delta_y = (1. / dy).abs();
+    // pieces shorter than this (in cells) are rounding artifacts near cell corners and edges
+    let t_epsilon = 1e-9 / dx.hypot(dy);
+
+    let mut t = 0.0;
+    while t < 1.0 {
+        let t_next = t_max_x.min(t_max_y).min(1.0);
+        if t_next - t > t_epsilon
+            && cx >= 0
+            && cy >= 0
+            && let Ok(code) = L::from_index_xy(cx as u32, cy as u32)
+        {
+            f(code, t, t_next);
+        }
+        t = t_next;
+        // step both axes at once when passing exactly through a corner
+        let (tx, ty) = (t_max_x, t_max_y);
+        if tx <= ty + t_epsilon {
+            cx += step_x;
+            t_max_x += t_delta_x;
+        }
+        if ty <= tx + t_epsilon {
+            cy += step_y;
+            t_max_y += t_delta_y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_horizontal_line() {
+        let code = StandardCode::from_str("64414277").unwrap();
+        let e = code.envelope();
+        let lat = (e.min().lat() + e.max().lat()) / 2.0;
+        let width = e.max().lng() - e.min().lng();
+        // from the center of 64414277 to the center of 64414370 (across a secondary boundary)
+        let line = [
+            LngLat::new(e.min().lng() + width * 0.5, lat),
+            LngLat::new(e.min().lng() + width * 3.5, lat),
+        ];
+        let cells = line_coverage::<StandardCode>(&line);
+        let codes = cells.iter().map(|c| c.code.to_string()).collect::<Vec<_>>();
+        assert_eq!(codes, ["64414277", "64414278", "64414279", "64414370"]);
+        assert!((cells[0].length_deg - width * 0.5).abs() < 1e-12);
+        assert!((cells[1].length_deg - width).abs() < 1e-12);
+        assert!((cells[2].length_deg - width).abs() < 1e-12);
+        assert!((cells[3].length_deg - width * 0.5).abs() < 1e-12);
+        // 45 seconds of longitude at 43.2°N is about 1.02 km
+        assert!(
+            (cells[1].length_m - 1_018.0).abs() < 5.0,
+            "{}",
+            cells[1].length_m
+        );
+
+        // reversed
+        let mut reversed = line;
+        reversed.reverse();
+        let cells = line_coverage::<StandardCode>(&reversed);
+        assert_eq!(cells.len(), 4);
+        assert_eq!(cells[0].code.to_string(), "64414370");
+    }
+
+    #[test]
+    fn test_diagonal_line() {
+        // exactly along the diagonal of a secondary code: passes through cell corners
+        let e = SecondaryCode::from_str("644142").unwrap().envelope();
+        let cells = line_coverage::<StandardCode>(&[e.min(), e.max()]);
+        assert_eq!(cells.len(), 10);
+        for (i, cell) in cells.iter().enumerate() {
+            assert_eq!(cell.code.x3() as usize, i);
+            assert_eq!(cell.code.y3() as usize, i);
+        }
+        let total: f64 = cells.iter().map(|c| c.length_deg).sum();
+        let expected = (e.max().lng() - e.min().lng()).hypot(e.max().lat() - e.min().lat());
+        assert!((total - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_polyline() {
+        let line = [
+            LngLat::new(139.7000, 35.6800),
+            LngLat::new(139.7100, 35.6900),
+            LngLat::new(139.7050, 35.7000),
+            LngLat::new(139.7300, 35.6950),
+        ];
+        let cells = line_coverage::<HalfCode>(&line);
+        // no gaps: consecutive cells are adjacent
+        for pair in cells.windows(2) {
+            let (ax, ay) = pair[0].code.index_xy();
+            let (bx, by) = pair[1].code.index_xy();
+            assert_eq!(ax.abs_diff(bx) + ay.abs_diff(by), 1);
+        }
+        let total: f64 = cells.iter().map(|c| c.length_deg).sum();
+        let expected: f64 = line
+            .windows(2)
+            .map(|s| (s[1].lng() - s[0].lng()).hypot(s[1].lat() - s[0].lat()))
+            .sum();
+        assert!((total - expected).abs() < 1e-9);
+        assert_eq!(cells[0].code, HalfCode::from_lnglat(line[0]).unwrap());
+        assert_eq!(
+            cells.last().unwrap().code,
+            HalfCode::from_lnglat(line[3]).unwrap()
+        );
+        assert!(line_coverage::<HalfCode>(&line[..1]).is_empty());
+    }
+}
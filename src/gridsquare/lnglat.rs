@@ -2,6 +2,8 @@
 //
 // Internal (lng, lat) values are multiplied by 30.0 to avoid floating point errors
 
+use super::MeshLevel;
+
 pub(crate) const MULTIPLYER: f64 = 30.0;
 
 #[derive(Clone, Copy, PartialEq)]
@@ -49,8 +51,8 @@ impl std::fmt::Display for LngLat {
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct LngLatBox {
-    min: LngLat,
-    max: LngLat,
+    pub(crate) min: LngLat,
+    pub(crate) max: LngLat,
 }
 
 impl LngLatBox {
@@ -65,6 +67,14 @@ impl LngLatBox {
         LngLatBox { min, max }
     }
 
+    /// Returns the smallest box containing all the points, or None if there are none
+    pub fn from_points(points: impl IntoIterator<Item = LngLat>) -> Option<Self> {
+        points
+            .into_iter()
+            .map(|p| LngLatBox { min: p, max: p })
+            .reduce(|a, b| a.union(&b))
+    }
+
     #[inline]
     pub fn min(&self) -> LngLat {
         self.min
@@ -75,6 +85,92 @@ impl LngLatBox {
         self.max
     }
 
+    /// Returns the width in degrees of longitude
+    #[inline]
+    pub fn width(&self) -> f64 {
+        (self.max.vlng - self.min.vlng) / MULTIPLYER
+    }
+
+    /// Returns the height in degrees of latitude
+    #[inline]
+    pub fn height(&self) -> f64 {
+        (self.max.vlat - self.min.vlat) / MULTIPLYER
+    }
+
+    #[inline]
+    pub fn center(&self) -> LngLat {
+        LngLat::new_raw(
+            (self.min.vlng + self.max.vlng) / 2.0,
+            (self.min.vlat + self.max.vlat) / 2.0,
+        )
+    }
+
+    /// Returns the four corners counter-clockwise, starting from the south-west corner
+    #[inline]
+    pub fn corners(&self) -> [LngLat; 4] {
+        [
+            self.min,
+            LngLat::new_raw(self.max.vlng, self.min.vlat),
+            self.max,
+            LngLat::new_raw(self.min.vlng, self.max.vlat),
+        ]
+    }
+
+    /// Returns the overlapping part of two boxes, or None if they do not intersect
+    ///
+    /// Boxes touching at an edge or a corner give a degenerate box, as with [`Self::intersects_box`].
+    #[inline]
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        self.intersects_box(other).then(|| LngLatBox {
+            min: LngLat::new_raw(
+                self.min.vlng.max(other.min.vlng),
+                self.min.vlat.max(other.min.vlat),
+            ),
+            max: LngLat::new_raw(
+                self.max.vlng.min(other.max.vlng),
+                self.max.vlat.min(other.max.vlat),
+            ),
+        })
+    }
+
+    /// Grows the box by the given margins in degrees on each side
+    ///
+    /// Negative margins shrink the box; a box shrunk past its center collapses to the center.
+    pub fn expand_by(&self, dlng: f64, dlat: f64) -> Self {
+        let center = self.center();
+        let (dlng, dlat) = (dlng * MULTIPLYER, dlat * MULTIPLYER);
+        let (min_vlng, max_vlng) = if self.min.vlng - dlng <= self.max.vlng + dlng {
+            (self.min.vlng - dlng, self.max.vlng + dlng)
+        } else {
+            (center.vlng, center.vlng)
+        };
+        let (min_vlat, max_vlat) = if self.min.vlat - dlat <= self.max.vlat + dlat {
+            (self.min.vlat - dlat, self.max.vlat + dlat)
+        } else {
+            (center.vlat, center.vlat)
+        };
+        LngLatBox {
+            min: LngLat::new_raw(min_vlng, min_vlat),
+            max: LngLat::new_raw(max_vlng, max_vlat),
+        }
+    }
+
+    /// Expands the box outward to the nearest cell boundaries of level `L`
+    pub fn snap_to_level<L: MeshLevel>(&self) -> Self {
+        let d = L::DIVISIONS as f64;
+        let (w, h) = (30.0 / d, 20.0 / d);
+        LngLatBox {
+            min: LngLat::new_raw(
+                (self.min.vlng / w).floor() * w,
+                (self.min.vlat / h).floor() * h,
+            ),
+            max: LngLat::new_raw(
+                (self.max.vlng / w).ceil() * w,
+                (self.max.vlat / h).ceil() * h,
+            ),
+        }
+    }
+
     #[inline]
     pub fn union(self, other: &Self) -> Self {
         LngLatBox {
@@ -115,8 +211,13 @@ impl LngLatBox {
 
     /// Divides this box into an N×N grid and returns the sub-box at position (x, y)
     pub fn split<const N: u8>(&self, x: u8, y: u8) -> Self {
-        let dlng = (self.max.vlng - self.min.vlng) / N as f64;
-        let dlat = (self.max.vlat - self.min.vlat) / N as f64;
+        self.split_mn(N as u32, N as u32, x as u32, y as u32)
+    }
+
+    /// Divides this box into `m` columns and `n` rows and returns the sub-box at position (x, y)
+    pub fn split_mn(&self, m: u32, n: u32, x: u32, y: u32) -> Self {
+        let dlng = (self.max.vlng - self.min.vlng) / m as f64;
+        let dlat = (self.max.vlat - self.min.vlat) / n as f64;
         Self {
             min: LngLat::new_raw(
                 self.min.vlng + dlng * x as f64,
@@ -128,11 +229,18 @@ impl LngLatBox {
             ),
         }
     }
+
+    /// Divides this box into `m` columns and `n` rows, yielding the sub-boxes row by row from the south-west
+    pub fn subdivide(&self, m: u32, n: u32) -> impl Iterator<Item = Self> {
+        let this = *self;
+        (0..n).flat_map(move |y| (0..m).map(move |x| this.split_mn(m, n, x, y)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gridsquare::{GridSquareCode, HalfCode, StandardCode, codes_in_box};
 
     #[test]
     fn test_lnglat() {
@@ -189,4 +297,86 @@ mod tests {
             LngLat::new(139.30, 35.6)
         )));
     }
+
+    #[test]
+    fn test_lnglat_box_algebra() {
+        let box1 = LngLatBox::new(LngLat::new(139.25, 35.5), LngLat::new(139.75, 36.0));
+        let box2 = LngLatBox::new(LngLat::new(139.5, 35.75), LngLat::new(140.0, 36.5));
+        assert_eq!(box1.width(), 0.5);
+        assert_eq!(box1.height(), 0.5);
+        assert_eq!(box1.center(), LngLat::new(139.5, 35.75));
+
+        assert_eq!(
+            box1.intersection(&box2),
+            Some(LngLatBox::new(
+                LngLat::new(139.5, 35.75),
+                LngLat::new(139.75, 36.0)
+            ))
+        );
+        assert_eq!(box1.intersection(&box1), Some(box1));
+        let far = LngLatBox::new(LngLat::new(141.0, 35.5), LngLat::new(142.0, 36.0));
+        assert_eq!(box1.intersection(&far), None);
+
+        assert_eq!(
+            box1.expand_by(0.25, 0.5),
+            LngLatBox::new(LngLat::new(139.0, 35.0), LngLat::new(140.0, 36.5))
+        );
+        assert_eq!(
+            box1.expand_by(-0.125, -1.0),
+            LngLatBox::new(LngLat::new(139.375, 35.75), LngLat::new(139.625, 35.75))
+        );
+
+        let points = [
+            LngLat::new(139.5, 36.0),
+            LngLat::new(139.25, 35.75),
+            LngLat::new(139.75, 35.5),
+        ];
+        assert_eq!(LngLatBox::from_points(points), Some(box1));
+        assert_eq!(LngLatBox::from_points([]), None);
+    }
+
+    #[test]
+    fn test_subdivide() {
+        let bounds = LngLatBox::new(LngLat::new(139.0, 35.0), LngLat::new(140.0, 36.0));
+        assert_eq!(bounds.split::<2>(1, 0), bounds.split_mn(2, 2, 1, 0));
+        let cell = bounds.split_mn(5, 4, 4, 1);
+        assert_eq!(
+            cell,
+            LngLatBox::new(LngLat::new(139.8, 35.25), LngLat::new(140.0, 35.5))
+        );
+        let cells = bounds.subdivide(5, 4).collect::<Vec<_>>();
+        assert_eq!(cells.len(), 20);
+        assert_eq!(cells[9], cell);
+        assert_eq!(cells[0].min(), bounds.min());
+        assert_eq!(cells[19].max(), bounds.max());
+    }
+
+    #[test]
+    fn test_snap_to_level() {
+        let bounds = LngLatBox::new(
+            LngLat::new(139.70001, 35.65001),
+            LngLat::new(139.77999, 35.69999),
+        );
+        let snapped = bounds.snap_to_level::<StandardCode>();
+        assert!(snapped.contains_box(&bounds));
+        // the snapped box is exactly the union of the cells intersecting the original
+        let codes = codes_in_box::<StandardCode>(bounds);
+        let union = codes.map(|c| c.envelope()).reduce(|a, b| a.union(&b));
+        let union = union.unwrap();
+        for (a, b) in [(snapped.min, union.min), (snapped.max, union.max)] {
+            assert!((a.vlng - b.vlng).abs() < 1e-9 && (a.vlat - b.vlat).abs() < 1e-9);
+        }
+
+        // already aligned boxes do not change
+        let aligned = HalfCode::from_lnglat(LngLat::new(139.7, 35.65))
+            .unwrap()
+            .envelope();
+        assert_eq!(aligned.snap_to_level::<HalfCode>(), aligned);
+        assert_eq!(
+            aligned.snap_to_level::<StandardCode>(),
+            StandardCode::from_lnglat(LngLat::new(139.7, 35.65))
+                .unwrap()
+                .envelope()
+        );
+    }
 }
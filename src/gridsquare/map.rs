@@ -0,0 +1,277 @@
+// Sparse maps from codes of mixed levels to values, with aggregation up the hierarchy
+
+use super::resample::{Cell, resample_cells};
+use super::*;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+
+/// A sparse map from codes of any level to values, kept in code order
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshMap<V> {
+    values: BTreeMap<LevelAndCode, V>,
+}
+
+impl<V> Default for MeshMap<V> {
+    fn default() -> Self {
+        Self {
+            values: BTreeMap::new(),
+        }
+    }
+}
+
+impl<V> MeshMap<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Sets the value of a code, returning the previous one
+    pub fn insert(&mut self, code: impl Into<LevelAndCode>, value: V) -> Option<V> {
+        self.values.insert(code.into(), value)
+    }
+
+    pub fn get(&self, code: impl Into<LevelAndCode>) -> Option<&V> {
+        self.values.get(&code.into())
+    }
+
+    pub fn get_mut(&mut self, code: impl Into<LevelAndCode>) -> Option<&mut V> {
+        self.values.get_mut(&code.into())
+    }
+
+    pub fn remove(&mut self, code: impl Into<LevelAndCode>) -> Option<V> {
+        self.values.remove(&code.into())
+    }
+
+    /// Returns the entries in code order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (LevelAndCode, &V)> {
+        self.values.iter().map(|(c, v)| (*c, v))
+    }
+
+    /// Returns the entries of the code and of the codes inside it, in code order
+    ///
+    /// `code` must be of the quad chain, whose descendants directly follow it in code order.
+    pub(crate) fn within(&self, code: LevelAndCode) -> impl Iterator<Item = (LevelAndCode, &V)> {
+        self.values
+            .range(code..)
+            .map(|(c, v)| (*c, v))
+            .take_while(move |(c, _)| code.contains(c))
+    }
+
+    /// Returns the entries of one level in code order
+    pub fn iter_level(&self, level: Level) -> impl DoubleEndedIterator<Item = (LevelAndCode, &V)> {
+        self.iter().filter(move |(c, _)| c.level() == level)
+    }
+
+    /// Aggregates the values into every ancestor of the codes, up to the primary level
+    ///
+    /// The usual ways of combining values are in [`aggregate`], e.g. [`aggregate::Sum`].
+    ///
+    /// The result has an entry for each code and each of its ancestors, so the aggregate of
+    /// any code is a single hash lookup. Values given at several levels all count toward their
+    /// common ancestors. Integrated (x5 and x2) codes roll up into their secondary codes; use
+    /// [`MeshMap::resample`] to aggregate into them.
+    pub fn roll_up<A: Aggregate<V>>(&self, aggregate: &A) -> RollUp<A::Output> {
+        // levels are processed from the finest, each feeding its parent level
+        let mut states: BTreeMap<Level, HashMap<LevelAndCode, A::State>> = BTreeMap::new();
+        for (code, value) in &self.values {
+            let state = aggregate.init(value);
+            let level = states.entry(code.level()).or_default();
+            merge_state(aggregate, level, *code, state);
+        }
+        let mut entries = Vec::new();
+        while let Some((_, level)) = states.pop_last() {
+            for (code, state) in level {
+                entries.push((code, aggregate.finish(&state)));
+                if let Some(parent) = code.parent() {
+                    let parents = states.entry(parent.level()).or_default();
+                    merge_state(aggregate, parents, parent, state);
+                }
+            }
+        }
+        entries.sort_unstable_by_key(|(code, _)| *code);
+        let index = entries
+            .iter()
+            .enumerate()
+            .map(|(i, (code, _))| (*code, i))
+            .collect();
+        RollUp { entries, index }
+    }
+}
+
+/// The aggregates of a [`MeshMap`] at every level, returned by [`MeshMap::roll_up`]
+///
+/// Entries are kept in code order, with a hash index for constant-time lookup.
+#[derive(Debug, Clone)]
+pub struct RollUp<T> {
+    entries: Vec<(LevelAndCode, T)>,
+    index: HashMap<LevelAndCode, usize>,
+}
+
+impl<T> RollUp<T> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the aggregate of the code, or None if no value lies within it
+    pub fn get(&self, code: impl Into<LevelAndCode>) -> Option<&T> {
+        let i = *self.index.get(&code.into())?;
+        Some(&self.entries[i].1)
+    }
+
+    /// Returns the entries in code order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (LevelAndCode, &T)> {
+        self.entries.iter().map(|(c, v)| (*c, v))
+    }
+
+    /// Returns the entries of one level in code order
+    pub fn iter_level(&self, level: Level) -> impl DoubleEndedIterator<Item = (LevelAndCode, &T)> {
+        self.iter().filter(move |(c, _)| c.level() == level)
+    }
+}
+
+impl<T> From<RollUp<T>> for MeshMap<T> {
+    fn from(roll_up: RollUp<T>) -> Self {
+        roll_up.entries.into_iter().collect()
+    }
+}
+
+impl<V: Copy + Into<f64>> MeshMap<V> {
+    /// Converts the values to codes of another level (see [`Resampling`])
+    ///
+    /// Codes of any level, including the integrated ones, can be converted to any other level.
+    /// The result has an entry for every code overlapping a code of the map.
+    pub fn resample(&self, level: Level, method: Resampling) -> MeshMap<f64> {
+        let sources = self.values.iter().map(|(code, value)| {
+            let (x, y) = code.index_xy();
+            let divisions = code.level().divisions();
+            (Cell { divisions, x, y }, (*value).into())
+        });
+        resample_cells(sources, level, method)
+            .into_iter()
+            .map(|((x, y), value)| {
+                let code = LevelAndCode::from_index_xy(level, x, y);
+                (code.expect("index is within the domain"), value)
+            })
+            .collect()
+    }
+}
+
+fn merge_state<V, A: Aggregate<V>>(
+    aggregate: &A,
+    states: &mut HashMap<LevelAndCode, A::State>,
+    code: LevelAndCode,
+    state: A::State,
+) {
+    match states.entry(code) {
+        Entry::Occupied(mut e) => aggregate.merge(e.get_mut(), state),
+        Entry::Vacant(e) => {
+            e.insert(state);
+        }
+    }
+}
+
+impl<C: Into<LevelAndCode>, V> FromIterator<(C, V)> for MeshMap<V> {
+    fn from_iter<I: IntoIterator<Item = (C, V)>>(iter: I) -> Self {
+        Self {
+            values: iter.into_iter().map(|(c, v)| (c.into(), v)).collect(),
+        }
+    }
+}
+
+/// A way of combining values, used by [`MeshMap::roll_up`] (see [`aggregate`] for the usual ones)
+pub trait Aggregate<V> {
+    /// Partial result for a group of values
+    type State;
+    type Output;
+
+    /// Returns the state for a single value
+    fn init(&self, value: &V) -> Self::State;
+
+    /// Adds the values of `other` to `state`
+    fn merge(&self, state: &mut Self::State, other: Self::State);
+
+    fn finish(&self, state: &Self::State) -> Self::Output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::aggregate::{Count, Max, Mean, Min, Sum};
+    use super::*;
+    use std::str::FromStr;
+
+    fn code(s: &str) -> LevelAndCode {
+        LevelAndCode::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_map() {
+        let mut map = MeshMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert(code("53394611"), 1), None);
+        assert_eq!(
+            map.insert(StandardCode::from_str("53394611").unwrap(), 2),
+            Some(1)
+        );
+        map.insert(code("533946112"), 3);
+        map.insert(code("6441"), 4);
+        *map.get_mut(code("533946112")).unwrap() += 1;
+        assert_eq!(map.get(code("533946112")), Some(&4));
+        assert_eq!(map.len(), 3);
+        assert_eq!(
+            map.iter().map(|(c, _)| c).collect::<Vec<_>>(),
+            [code("53394611"), code("533946112"), code("6441")]
+        );
+        assert_eq!(
+            map.iter_level(Level::Half).collect::<Vec<_>>(),
+            [(code("533946112"), &4)]
+        );
+        assert_eq!(map.remove(code("6441")), Some(4));
+        assert_eq!(map.get(code("6441")), None);
+    }
+
+    #[test]
+    fn test_roll_up() {
+        let map = [
+            (code("5339461111"), 1.0),
+            (code("5339461112"), 2.0),
+            (code("5339461121"), 3.0),
+            (code("5339461211"), 6.0),
+            (code("53394711"), 8.0),
+        ]
+        .into_iter()
+        .collect::<MeshMap<f64>>();
+
+        let sum = map.roll_up(&Sum);
+        assert_eq!(sum.get(code("533946111")), Some(&3.0));
+        assert_eq!(sum.get(code("53394611")), Some(&6.0));
+        assert_eq!(sum.get(code("533946")), Some(&12.0));
+        assert_eq!(sum.get(code("5339")), Some(&20.0));
+        assert_eq!(sum.get(code("5339461113")), None);
+        assert_eq!(sum.iter_level(Level::Quarter).count(), 4);
+        assert_eq!(
+            sum.iter_level(Level::Secondary).collect::<Vec<_>>(),
+            [(code("533946"), &12.0), (code("533947"), &8.0)]
+        );
+        assert_eq!(sum.len(), 4 + 3 + 3 + 2 + 1);
+        assert!(sum.iter().map(|(c, _)| c).is_sorted());
+        let sum = MeshMap::from(sum);
+        assert_eq!(sum.get(code("5339")), Some(&20.0));
+        assert_eq!(sum.len(), 4 + 3 + 3 + 2 + 1);
+
+        assert_eq!(map.roll_up(&Mean).get(code("533946")), Some(&3.0));
+        assert_eq!(map.roll_up(&Min).get(code("5339")), Some(&1.0));
+        assert_eq!(map.roll_up(&Max).get(code("533946")), Some(&6.0));
+        assert_eq!(map.roll_up(&Count).get(code("5339")), Some(&5));
+    }
+}
@@ -0,0 +1,182 @@
+// Point-in-region matching against labeled mesh sets
+
+use super::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Marks a table entry as a leaf; the remaining bits are an index into the label slices
+const LEAF: u32 = 1 << 31;
+
+/// Assigns points to labeled zones defined as [`MeshSet`]s
+///
+/// The zones are compiled into a lookup table following the code hierarchy: one array
+/// indexed by primary code, then one small array per subdivided code. A lookup costs at most
+/// one array access per level and returns a precomputed slice of labels.
+#[derive(Debug, Clone)]
+pub struct MeshMatcher<Label> {
+    /// Entries for all primary codes, indexed by ordinal
+    root: Vec<u32>,
+    /// Entries for the children of subdivided codes; a non-leaf entry is the start of its
+    /// `split²` children
+    children: Vec<u32>,
+    labels: Vec<Label>,
+    slices: Vec<(u32, u32)>,
+}
+
+impl<Label: Clone> MeshMatcher<Label> {
+    /// Builds the matcher; labels of a point are returned in the order the zones are given
+    pub fn new<'a>(zones: impl IntoIterator<Item = (Label, &'a MeshSet)>) -> Self {
+        let mut zone_labels = Vec::new();
+        let mut full: BTreeMap<LevelAndCode, Vec<usize>> = BTreeMap::new();
+        let mut subdivided: HashSet<LevelAndCode> = HashSet::new();
+        for (i, (label, set)) in zones.into_iter().enumerate() {
+            zone_labels.push(label);
+            for code in set.iter() {
+                full.entry(code).or_default().push(i);
+                let mut c = code;
+                while let Some(parent) = c.parent() {
+                    if !subdivided.insert(parent) {
+                        break;
+                    }
+                    c = parent;
+                }
+            }
+        }
+
+        let mut builder = Builder {
+            full,
+            subdivided,
+            zone_labels,
+            matcher: MeshMatcher {
+                root: Vec::new(),
+                children: Vec::new(),
+                labels: Vec::new(),
+                slices: Vec::new(),
+            },
+            slice_ids: HashMap::new(),
+        };
+        let empty = builder.slice(&[]);
+        let mut root = vec![empty; 10000];
+        for (ordinal, entry) in root.iter_mut().enumerate() {
+            let primary = PrimaryCode::from_ordinal(ordinal as u32).expect("valid ordinal");
+            *entry = builder.entry(primary.into(), &[]);
+        }
+        builder.matcher.root = root;
+        builder.matcher
+    }
+
+    /// Returns the labels of the zones containing the point
+    pub fn lookup(&self, lnglat: LngLat) -> &[Label] {
+        let Ok(code) = EighthCode::from_lnglat(lnglat) else {
+            return &[];
+        };
+        let (x, y) = code.index_xy();
+        let d = EighthCode::DIVISIONS;
+        let mut entry = self.root[(y / d * 100 + x / d) as usize];
+        let mut level = Level::Primary;
+        while entry & LEAF == 0 {
+            level = level.child().expect("no entries below the eighth level");
+            let (split, ratio) = (level.split(), d / level.divisions());
+            let (dx, dy) = ((x / ratio) % split, (y / ratio) % split);
+            entry = self.children[(entry + dy * split + dx) as usize];
+        }
+        let (start, end) = self.slices[(entry & !LEAF) as usize];
+        &self.labels[start as usize..end as usize]
+    }
+}
+
+struct Builder<Label> {
+    /// Zones having each code as a member
+    full: BTreeMap<LevelAndCode, Vec<usize>>,
+    /// Codes containing members of a finer level
+    subdivided: HashSet<LevelAndCode>,
+    zone_labels: Vec<Label>,
+    matcher: MeshMatcher<Label>,
+    slice_ids: HashMap<Vec<usize>, u32>,
+}
+
+impl<Label: Clone> Builder<Label> {
+    /// Builds the entry for a code inside the given zones
+    fn entry(&mut self, code: LevelAndCode, inherited: &[usize]) -> u32 {
+        let mut zones = inherited.to_vec();
+        if let Some(own) = self.full.get(&code) {
+            zones.extend(own);
+            zones.sort_unstable();
+        }
+        if !self.subdivided.contains(&code) {
+            return self.slice(&zones);
+        }
+        let start = self.matcher.children.len();
+        let children = code.children().collect::<Vec<_>>();
+        self.matcher.children.resize(start + children.len(), 0);
+        for (i, child) in children.into_iter().enumerate() {
+            let entry = self.entry(child, &zones);
+            self.matcher.children[start + i] = entry;
+        }
+        assert!(start < LEAF as usize, "too many subdivided codes");
+        start as u32
+    }
+
+    /// Returns the leaf entry for a set of zones, sharing slices between equal sets
+    fn slice(&mut self, zones: &[usize]) -> u32 {
+        if let Some(id) = self.slice_ids.get(zones) {
+            return LEAF | id;
+        }
+        let start = self.matcher.labels.len() as u32;
+        let labels = zones.iter().map(|i| self.zone_labels[*i].clone());
+        self.matcher.labels.extend(labels);
+        let id = self.matcher.slices.len() as u32;
+        self.matcher
+            .slices
+            .push((start, self.matcher.labels.len() as u32));
+        self.slice_ids.insert(zones.to_vec(), id);
+        LEAF | id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn set(codes: &[&str]) -> MeshSet {
+        codes
+            .iter()
+            .map(|c| LevelAndCode::from_str(c).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_lookup() {
+        let tokyo = set(&["533946", "53394711"]);
+        let station = set(&["53394611", "5339461234"]);
+        let sapporo = set(&["64414278"]);
+        let matcher = MeshMatcher::new([
+            ("tokyo", &tokyo),
+            ("station", &station),
+            ("sapporo", &sapporo),
+        ]);
+
+        let point = |code: &str| LevelAndCode::from_str(code).unwrap().center();
+        assert_eq!(matcher.lookup(point("53394611")), ["tokyo", "station"]);
+        assert_eq!(matcher.lookup(point("5339461234")), ["tokyo", "station"]);
+        assert_eq!(matcher.lookup(point("5339461233")), ["tokyo"]);
+        assert_eq!(matcher.lookup(point("53394799")), [] as [&str; 0]);
+        assert_eq!(matcher.lookup(point("53394711")), ["tokyo"]);
+        assert_eq!(matcher.lookup(point("644142781")), ["sapporo"]);
+        assert!(matcher.lookup(point("5440")).is_empty());
+        assert!(matcher.lookup(LngLat::new(0.0, 0.0)).is_empty());
+
+        // agrees with the sets themselves
+        let zones = [("tokyo", &tokyo), ("station", &station)];
+        let e = LevelAndCode::from_str("533946").unwrap().envelope();
+        for code in codes_in_box::<QuarterCode>(e).step_by(7) {
+            let p = code.center();
+            let expected = zones
+                .iter()
+                .filter(|(_, s)| s.contains_point(p))
+                .map(|(l, _)| *l)
+                .collect::<Vec<_>>();
+            assert_eq!(matcher.lookup(p), expected);
+        }
+    }
+}
@@ -1,10 +1,54 @@
 // JIS X 0410:2002 地域メッシュコード (Grid Square Code)
 
+pub mod aggregate;
+mod bitmap;
+mod boundary;
+mod circle;
 mod code;
+mod components;
 mod constants;
+mod cover;
+mod custom;
+mod dissolve;
+mod ellipsoid;
+mod grid;
+mod interpolate;
 mod iterator;
+mod level;
+mod line;
 mod lnglat;
+mod map;
+mod matcher;
+mod morphology;
+mod overlay;
+#[cfg(feature = "rayon")]
+mod par;
+mod polygon;
+mod random;
+mod reader;
+mod resample;
+mod set;
 
+pub use bitmap::*;
+pub use boundary::*;
+pub use circle::*;
 pub use code::*;
+pub use components::*;
+pub use cover::*;
+pub use custom::*;
+pub use dissolve::*;
+pub use grid::*;
+pub use interpolate::*;
 pub use iterator::*;
+pub use level::*;
+pub use line::*;
 pub use lnglat::*;
+pub use map::*;
+pub use matcher::*;
+pub use overlay::*;
+#[cfg(feature = "rayon")]
+pub use par::*;
+pub use polygon::*;
+pub use reader::*;
+pub use resample::*;
+pub use set::*;
@@ -0,0 +1,322 @@
+// Morphological operations on sets of codes
+
+use super::*;
+
+impl<L: MeshLevel> MeshBitmap<L> {
+    /// Adds every code within `k` steps of the set
+    ///
+    /// Steps cross parent boundaries; with [`Connectivity::Eight`] the set grows by `k` cells
+    /// diagonally as well.
+    pub fn dilate(&self, k: u32, connectivity: Connectivity) -> Self {
+        let mut result = self.clone();
+        let mut frontier = self.clone();
+        for _ in 0..k {
+            let mut next = MeshBitmap::new();
+            for code in frontier.iter() {
+                for n in code.neighbors(connectivity) {
+                    if result.insert(n) {
+                        next.insert(n);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        result
+    }
+
+    /// Keeps only the codes whose neighborhood of `k` steps is entirely in the set
+    ///
+    /// Cells outside the domain count as outside the set.
+    pub fn erode(&self, k: u32, connectivity: Connectivity) -> Self {
+        let expected = connectivity.offsets().len();
+        let mut result = self.clone();
+        for _ in 0..k {
+            result = result
+                .iter()
+                .filter(|code| {
+                    let mut neighbors = code.neighbors(connectivity);
+                    let mut count = 0;
+                    let all = neighbors.all(|n| {
+                        count += 1;
+                        result.contains(n)
+                    });
+                    all && count == expected
+                })
+                .collect();
+        }
+        result
+    }
+
+    /// Erosion followed by dilation: removes parts thinner than `2k + 1` cells
+    pub fn open(&self, k: u32, connectivity: Connectivity) -> Self {
+        self.erode(k, connectivity).dilate(k, connectivity)
+    }
+
+    /// Dilation followed by erosion: fills gaps narrower than `2k + 1` cells
+    pub fn close(&self, k: u32, connectivity: Connectivity) -> Self {
+        self.dilate(k, connectivity).erode(k, connectivity)
+    }
+
+    /// Adds the holes: groups of codes outside the set that cannot reach the outside of its
+    /// bounding box by `connectivity` steps through codes outside the set
+    ///
+    /// A hole is enclosed by codes connected the other way (by corners when steps are
+    /// [`Connectivity::Four`]), so each group of those is handled on its own and the scratch
+    /// space spans one group at a time rather than the whole set.
+    pub fn fill_holes(&self, connectivity: Connectivity) -> Self {
+        let barrier = match connectivity {
+            Connectivity::Four => Connectivity::Eight,
+            Connectivity::Eight => Connectivity::Four,
+        };
+        let components = connected_components(self.iter(), barrier);
+        let mut result = self.clone();
+        for (id, component) in components.components().iter().enumerate() {
+            // at least 4 codes are needed around a hole
+            if component.size >= 4 {
+                fill_enclosed(components.codes_of(id), connectivity, &mut result);
+            }
+        }
+        result
+    }
+}
+
+/// Adds to `result` the codes enclosed by `codes` that cannot reach the outside of their
+/// bounding box by `connectivity` steps
+fn fill_enclosed<L: MeshLevel>(
+    codes: impl Iterator<Item = L> + Clone,
+    connectivity: Connectivity,
+    result: &mut MeshBitmap<L>,
+) {
+    let Some((x0, y0, x1, y1)) = codes
+        .clone()
+        .map(|c| c.index_xy())
+        .fold(None, |acc, (x, y)| {
+            let (ax, ay, bx, by) = acc.unwrap_or((x, y, x, y));
+            Some((ax.min(x), ay.min(y), bx.max(x), by.max(y)))
+        })
+    else {
+        return;
+    };
+    // the bounding box grown by one cell, so that the outside surrounds the codes
+    let (x0, y0) = (x0 as i64 - 1, y0 as i64 - 1);
+    let (w, h) = (x1 as i64 - x0 + 2, y1 as i64 - y0 + 2);
+    let index = |x: i64, y: i64| ((y - y0) * w + (x - x0)) as usize;
+    let mut blocked = vec![0u64; (w * h) as usize / 64 + 1];
+    let is_set = |bits: &[u64], i: usize| bits[i / 64] >> (i % 64) & 1 == 1;
+    let set = |bits: &mut [u64], i: usize| bits[i / 64] |= 1 << (i % 64);
+    for code in codes {
+        let (x, y) = code.index_xy();
+        set(&mut blocked, index(x as i64, y as i64));
+    }
+
+    // cells are marked when pushed, so each is pushed at most once
+    let mut stack = Vec::new();
+    let visit = |blocked: &mut [u64], stack: &mut Vec<(i64, i64)>, x: i64, y: i64| {
+        let i = index(x, y);
+        if !is_set(blocked, i) {
+            set(blocked, i);
+            stack.push((x, y));
+        }
+    };
+    for x in x0..x0 + w {
+        visit(&mut blocked, &mut stack, x, y0);
+        visit(&mut blocked, &mut stack, x, y0 + h - 1);
+    }
+    for y in y0..y0 + h {
+        visit(&mut blocked, &mut stack, x0, y);
+        visit(&mut blocked, &mut stack, x0 + w - 1, y);
+    }
+    while let Some((x, y)) = stack.pop() {
+        for (dx, dy) in connectivity.offsets() {
+            let (nx, ny) = (x + *dx as i64, y + *dy as i64);
+            if nx >= x0 && ny >= y0 && nx < x0 + w && ny < y0 + h {
+                visit(&mut blocked, &mut stack, nx, ny);
+            }
+        }
+    }
+
+    for y in y0 + 1..y0 + h - 1 {
+        for x in x0 + 1..x0 + w - 1 {
+            if !is_set(&blocked, index(x, y))
+                && let Ok(code) = L::from_index_xy(x as u32, y as u32)
+            {
+                result.insert(code);
+            }
+        }
+    }
+}
+
+/// Sets of mixed levels have no single step size, so each operation works on the codes of
+/// level `L` covering the set (see [`MeshSet::to_level`]) and normalizes the result
+impl MeshSet {
+    fn bitmap<L: MeshLevel>(&self) -> MeshBitmap<L> {
+        self.to_level::<L>().into_iter().collect()
+    }
+
+    /// Same as [`MeshBitmap::dilate`] with steps of level `L`
+    pub fn dilate<L: MeshLevel + Into<LevelAndCode>>(
+        &self,
+        k: u32,
+        connectivity: Connectivity,
+    ) -> MeshSet {
+        self.bitmap::<L>().dilate(k, connectivity).iter().collect()
+    }
+
+    /// Same as [`MeshBitmap::erode`] with steps of level `L`
+    ///
+    /// Members finer than `L` are first replaced by their ancestor, as in [`MeshSet::expand`].
+    pub fn erode<L: MeshLevel + Into<LevelAndCode>>(
+        &self,
+        k: u32,
+        connectivity: Connectivity,
+    ) -> MeshSet {
+        self.bitmap::<L>().erode(k, connectivity).iter().collect()
+    }
+
+    /// Same as [`MeshBitmap::open`] with steps of level `L`
+    pub fn open<L: MeshLevel + Into<LevelAndCode>>(
+        &self,
+        k: u32,
+        connectivity: Connectivity,
+    ) -> MeshSet {
+        self.bitmap::<L>().open(k, connectivity).iter().collect()
+    }
+
+    /// Same as [`MeshBitmap::close`] with steps of level `L`
+    pub fn close<L: MeshLevel + Into<LevelAndCode>>(
+        &self,
+        k: u32,
+        connectivity: Connectivity,
+    ) -> MeshSet {
+        self.bitmap::<L>().close(k, connectivity).iter().collect()
+    }
+
+    /// Same as [`MeshBitmap::fill_holes`] at level `L`
+    pub fn fill_holes<L: MeshLevel + Into<LevelAndCode>>(
+        &self,
+        connectivity: Connectivity,
+    ) -> MeshSet {
+        self.bitmap::<L>().fill_holes(connectivity).iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// A rectangle of standard codes within a secondary, given by offsets (x, y) and size
+    fn rect(x0: u32, y0: u32, w: u32, h: u32) -> MeshBitmap<StandardCode> {
+        let (x, y) = SecondaryCode::from_str("533946").unwrap().index_xy();
+        (0..w * h)
+            .map(|i| StandardCode::from_index_xy(x * 10 + x0 + i % w, y * 10 + y0 + i / w).unwrap())
+            .collect()
+    }
+
+    fn square(x0: u32, y0: u32, size: u32) -> MeshBitmap<StandardCode> {
+        rect(x0, y0, size, size)
+    }
+
+    #[test]
+    fn test_dilate_erode() {
+        let code = StandardCode::from_str("53394699").unwrap();
+        let single = [code].into_iter().collect::<MeshBitmap<_>>();
+        // crossing into the neighboring secondaries
+        let diamond = single.dilate(2, Connectivity::Four);
+        assert_eq!(diamond.len(), 13);
+        assert!(diamond.contains(code.neighbor(2, 0).unwrap()));
+        assert!(diamond.contains(StandardCode::from_str("53394790").unwrap()));
+        assert!(!diamond.contains(code.neighbor(2, 1).unwrap()));
+        let block = single.dilate(2, Connectivity::Eight);
+        assert_eq!(block.len(), 25);
+        assert!(block.contains(code.neighbor(2, -2).unwrap()));
+
+        assert_eq!(block.erode(2, Connectivity::Eight), single);
+        assert_eq!(diamond.erode(2, Connectivity::Four), single);
+        assert!(single.erode(1, Connectivity::Four).is_empty());
+        assert_eq!(
+            square(2, 2, 5).erode(1, Connectivity::Eight),
+            square(3, 3, 3)
+        );
+        assert_eq!(single.dilate(0, Connectivity::Four), single);
+    }
+
+    #[test]
+    fn test_open_close() {
+        // a square with a one-cell spur
+        let mut spur = square(2, 2, 5);
+        spur.insert(StandardCode::from_str("53394627").unwrap());
+        assert_eq!(spur.open(1, Connectivity::Eight), square(2, 2, 5));
+
+        // two squares with a one-cell gap between them
+        let gap = square(1, 1, 3).union(&square(5, 1, 3));
+        let closed = gap.close(1, Connectivity::Four);
+        // the diamond only bridges the middle of the gap
+        assert_eq!(closed.difference(&gap).len(), 1);
+        assert!(closed.contains(StandardCode::from_str("53394624").unwrap()));
+        assert_eq!(gap.close(1, Connectivity::Eight), rect(1, 1, 7, 3));
+    }
+
+    #[test]
+    fn test_fill_holes() {
+        // a ring of standards around a 3×3 hole
+        let ring = square(2, 2, 5).difference(&square(3, 3, 3));
+        let filled = ring.fill_holes(Connectivity::Four);
+        assert_eq!(filled, square(2, 2, 5));
+        assert_eq!(ring.fill_holes(Connectivity::Eight), filled);
+
+        // without the corner 53394666 the hole leaks diagonally
+        let mut leaky = ring.clone();
+        leaky.remove(StandardCode::from_str("53394666").unwrap());
+        assert_eq!(
+            leaky.fill_holes(Connectivity::Four),
+            leaky.union(&square(3, 3, 3))
+        );
+        assert_eq!(leaky.fill_holes(Connectivity::Eight), leaky);
+        assert!(
+            MeshBitmap::<StandardCode>::new()
+                .fill_holes(Connectivity::Four)
+                .is_empty()
+        );
+
+        // holes far apart, and a ring inside the hole of another
+        let far = |code: &str| {
+            let (x, y) = StandardCode::from_str(code).unwrap().index_xy();
+            let ring = (0..9)
+                .filter(|i| *i != 4)
+                .map(move |i| StandardCode::from_index_xy(x + i % 3, y + i / 3).unwrap());
+            ring.collect::<MeshBitmap<_>>()
+        };
+        let rings = far("53394611").union(&far("64414278"));
+        let filled = rings.fill_holes(Connectivity::Four);
+        assert_eq!(filled.len(), 18);
+        let nested = square(0, 0, 9).difference(&square(1, 1, 7)).union(&ring);
+        assert_eq!(nested.fill_holes(Connectivity::Eight), square(0, 0, 9));
+    }
+
+    #[test]
+    fn test_mesh_set() {
+        // a ring of standards around a 3×3 hole
+        let ring = square(2, 2, 5).difference(&square(3, 3, 3));
+        let set = ring.iter().collect::<MeshSet>();
+        let filled = set.fill_holes::<StandardCode>(Connectivity::Four);
+        assert_eq!(filled.len(), 25);
+        assert_eq!(
+            filled.to_level::<StandardCode>(),
+            square(2, 2, 5).iter().collect::<Vec<_>>()
+        );
+
+        // steps of the finer level grow a coarse member by a fringe of that level
+        let standard = LevelAndCode::from_str("53394655").unwrap();
+        let set = [standard].into_iter().collect::<MeshSet>();
+        let grown = set.dilate::<HalfCode>(1, Connectivity::Eight);
+        assert!(grown.contains(standard));
+        assert_eq!(grown.to_level::<HalfCode>().len(), 16);
+        assert_eq!(grown.erode::<HalfCode>(1, Connectivity::Eight), set);
+        assert_eq!(
+            set.open::<StandardCode>(1, Connectivity::Four),
+            MeshSet::new()
+        );
+        assert_eq!(set.close::<HalfCode>(1, Connectivity::Four), set);
+    }
+}
@@ -0,0 +1,96 @@
+// Area-fraction overlay between cells and polygons
+
+use super::*;
+
+/// A cell intersecting a polygon, with the share of the cell's area covered by it
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayCell<L> {
+    pub code: L,
+    /// Fraction of the cell's area covered by the polygon, in (0, 1]
+    pub fraction: f64,
+    /// Area in m² of the intersection
+    pub area_m2: f64,
+}
+
+/// Returns the cells of level `L` intersecting the polygons, with the covered fraction of each cell
+///
+/// Areas are measured on the GRS80 ellipsoid, treating polygon edges as straight lines in (lng, lat).
+pub fn polygon_overlay<L: MeshLevel>(
+    polygon: &MultiPolygon,
+) -> impl Iterator<Item = OverlayCell<L>> {
+    let primaries = polygon.bounds().map(primaries_from_bounds);
+    primaries.into_iter().flatten().flat_map(move |prim| {
+        L::iter_within(prim, polygon).map(|(code, patch, coverage)| {
+            let cell_area = code.area_m2();
+            let area_m2 = match coverage {
+                Coverage::Full => cell_area,
+                Coverage::Partial => polygon.clipped_area_m2(&patch).min(cell_area),
+            };
+            OverlayCell {
+                code,
+                fraction: area_m2 / cell_area,
+                area_m2,
+            }
+        })
+    })
+}
+
+/// Same as [`polygon_overlay`], also returning the part of the polygons inside each cell
+pub fn polygon_overlay_with_geometry<L: MeshLevel>(
+    polygon: &MultiPolygon,
+) -> impl Iterator<Item = (OverlayCell<L>, MultiPolygon)> {
+    polygon_overlay::<L>(polygon).map(move |cell| {
+        let clipped = polygon.clip(&cell.code.envelope());
+        (cell, clipped)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_polygon_overlay() {
+        // lower-right half of a secondary code, split along its diagonal
+        let secondary = SecondaryCode::from_str("533946").unwrap();
+        let envelope = secondary.envelope();
+        let triangle = MultiPolygon::from(Polygon::new(envelope.corners()[..3].to_vec(), vec![]));
+
+        let cells = polygon_overlay::<StandardCode>(&triangle).collect::<Vec<_>>();
+        assert_eq!(cells.len(), 55);
+        for cell in &cells {
+            let (x3, y3) = (cell.code.x3(), cell.code.y3());
+            if x3 == y3 {
+                // diagonal cells are cut in half (slightly off due to the ellipsoid)
+                assert!((cell.fraction - 0.5).abs() < 1e-3, "{}", cell.fraction);
+            } else {
+                assert_eq!(cell.fraction, 1.0);
+            }
+        }
+        let total: f64 = cells.iter().map(|c| c.area_m2).sum();
+        assert!((total / triangle.area_m2() - 1.0).abs() < 1e-9);
+        let secondary_area = secondary.area_m2();
+        assert!((total / secondary_area - 0.5).abs() < 1e-3);
+
+        let (cell, clipped) = polygon_overlay_with_geometry::<StandardCode>(&triangle)
+            .find(|(c, _)| c.code.to_string() == "53394633")
+            .unwrap();
+        assert_eq!(clipped.0.len(), 1);
+        assert!((clipped.area_m2() - cell.area_m2).abs() < 1e-6);
+        assert_eq!(clipped.bounds(), Some(cell.code.envelope()));
+    }
+
+    #[test]
+    fn test_overlay_with_hole() {
+        let secondary = SecondaryCode::from_str("533946").unwrap();
+        let code = HalfCode::from_str("533946551").unwrap();
+        let hole = code.envelope();
+        let polygon = MultiPolygon::from(Polygon::new(secondary.ring(1), vec![code.ring(1)]));
+        let cells = polygon_overlay::<StandardCode>(&polygon).collect::<Vec<_>>();
+        assert_eq!(cells.len(), 100);
+        let cell = cells.iter().find(|c| c.code == code.standard()).unwrap();
+        assert!((cell.fraction - 0.75).abs() < 1e-4, "{}", cell.fraction);
+        assert_eq!(polygon.clip(&hole).0.len(), 0);
+    }
+}
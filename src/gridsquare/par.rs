@@ -0,0 +1,145 @@
+// Parallel patch iteration (requires the `rayon` feature)
+//
+// Work is split per secondary code (per primary code for the primary level). Collecting with
+// `collect::<Vec<_>>()` yields the same order as the sequential iterators.
+
+use super::*;
+use rayon::iter::Either;
+use rayon::prelude::*;
+
+/// Restricts a boundary to the interior of a single secondary code
+#[derive(Clone, Copy)]
+struct WithinSecondary<B> {
+    boundary: B,
+    secondary: LngLatBox,
+}
+
+impl<B: Boundary> Boundary for WithinSecondary<B> {
+    #[inline]
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        overlaps(&self.secondary, patch) && self.boundary.intersects(patch)
+    }
+
+    #[inline]
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        self.secondary.contains_box(patch) && self.boundary.contains(patch)
+    }
+}
+
+/// Parallel version of [`patches`]
+pub fn par_patches<L: MeshLevel + Send>(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy + Send + Sync,
+) -> impl ParallelIterator<Item = (L, LngLatBox)> {
+    let primaries = primary_iter.collect::<Vec<_>>();
+    if L::DIVISIONS < SecondaryCode::DIVISIONS {
+        Either::Left(primaries.into_par_iter().flat_map_iter(move |prim| {
+            L::iter_within(prim, boundary).map(|(code, patch, _)| (code, patch))
+        }))
+    } else {
+        let secondaries = primaries
+            .into_iter()
+            .flat_map(SecondaryCode::iter_children_of)
+            .collect::<Vec<_>>();
+        Either::Right(secondaries.into_par_iter().flat_map_iter(move |sec| {
+            let within = WithinSecondary {
+                boundary,
+                secondary: sec.envelope(),
+            };
+            L::iter_within(sec.primary, within).map(|(code, patch, _)| (code, patch))
+        }))
+    }
+}
+
+/// Parallel version of [`codes`]
+pub fn par_codes<L: MeshLevel + Send>(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy + Send + Sync,
+) -> impl ParallelIterator<Item = L> {
+    par_patches::<L>(primary_iter, boundary).map(|(code, _)| code)
+}
+
+pub fn par_primary_patches(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy + Send + Sync,
+) -> impl ParallelIterator<Item = (PrimaryCode, LngLatBox)> {
+    par_patches(primary_iter, boundary)
+}
+
+pub fn par_secondary_patches(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy + Send + Sync,
+) -> impl ParallelIterator<Item = (SecondaryCode, LngLatBox)> {
+    par_patches(primary_iter, boundary)
+}
+
+pub fn par_standard_patches(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy + Send + Sync,
+) -> impl ParallelIterator<Item = (StandardCode, LngLatBox)> {
+    par_patches(primary_iter, boundary)
+}
+
+pub fn par_half_patches(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy + Send + Sync,
+) -> impl ParallelIterator<Item = (HalfCode, LngLatBox)> {
+    par_patches(primary_iter, boundary)
+}
+
+pub fn par_quarter_patches(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy + Send + Sync,
+) -> impl ParallelIterator<Item = (QuarterCode, LngLatBox)> {
+    par_patches(primary_iter, boundary)
+}
+
+pub fn par_eighth_patches(
+    primary_iter: impl Iterator<Item = PrimaryCode>,
+    boundary: impl Boundary + Copy + Send + Sync,
+) -> impl ParallelIterator<Item = (EighthCode, LngLatBox)> {
+    par_patches(primary_iter, boundary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_patches() {
+        let boundary = LngLatBox::new(
+            LngLat::new(141.305438074, 42.939466350),
+            LngLat::new(141.563765511, 43.129434849),
+        );
+
+        assert_eq!(
+            par_primary_patches(primaries_in_land(), Some(boundary)).count(),
+            1
+        );
+        assert_eq!(
+            par_secondary_patches(primaries_in_land(), Some(boundary)).count(),
+            9
+        );
+        assert_eq!(
+            par_standard_patches(primaries_in_land(), Some(boundary)).count(),
+            528
+        );
+        assert_eq!(
+            par_half_patches(primaries_in_land(), Some(boundary)).count(),
+            2021
+        );
+        assert_eq!(
+            par_quarter_patches(primaries_in_land(), Some(boundary)).count(),
+            7812
+        );
+
+        // same order as the sequential version
+        let par = par_eighth_patches(primaries_in_land(), Some(boundary)).collect::<Vec<_>>();
+        let seq = eighth_patches(primaries_in_land(), Some(boundary)).collect::<Vec<_>>();
+        assert_eq!(par, seq);
+
+        let par = par_codes::<SecondaryCode>(primaries_in_land(), None).collect::<Vec<_>>();
+        let seq = codes::<SecondaryCode>(primaries_in_land(), None).collect::<Vec<_>>();
+        assert_eq!(par, seq);
+    }
+}
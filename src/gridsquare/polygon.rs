@@ -0,0 +1,495 @@
+// Polygons and polygon coverage
+//
+// Polygons are handled in the (lng, lat) plane, so edges are straight lines in degrees,
+// the same way cell edges are.
+
+use super::boundary::overlaps;
+use super::ellipsoid;
+use super::lnglat::MULTIPLYER;
+use super::*;
+
+/// A polygon with an exterior ring and holes
+///
+/// Rings may be given either closed or open, in any orientation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    exterior: Vec<LngLat>,
+    interiors: Vec<Vec<LngLat>>,
+    bounds: Option<LngLatBox>,
+}
+
+impl Polygon {
+    pub fn new(exterior: Vec<LngLat>, interiors: Vec<Vec<LngLat>>) -> Self {
+        let bounds = LngLatBox::from_points(exterior.iter().copied());
+        Self {
+            exterior: open_ring(exterior),
+            interiors: interiors.into_iter().map(open_ring).collect(),
+            bounds,
+        }
+    }
+
+    /// Returns the exterior ring (without the closing vertex)
+    pub fn exterior(&self) -> &[LngLat] {
+        &self.exterior
+    }
+
+    /// Returns the holes (without the closing vertices)
+    pub fn interiors(&self) -> &[Vec<LngLat>] {
+        &self.interiors
+    }
+
+    /// Returns the bounding box of the exterior ring, or None if it is empty
+    pub fn bounds(&self) -> Option<LngLatBox> {
+        self.bounds
+    }
+
+    /// Returns the area of the polygon inside the box, in squared internal units
+    pub(crate) fn clipped_area(&self, rect: &LngLatBox) -> f64 {
+        if !self.bounds.is_some_and(|b| overlaps(&b, rect)) {
+            return 0.0;
+        }
+        let mut buf = ClipBuffer::default();
+        let exterior = ring_area(buf.clip(&self.exterior, rect)).abs();
+        if exterior == 0.0 {
+            return 0.0;
+        }
+        let holes: f64 = self
+            .interiors
+            .iter()
+            .map(|hole| ring_area(buf.clip(hole, rect)).abs())
+            .sum();
+        (exterior - holes).max(0.0)
+    }
+
+    /// Returns the part of the polygon inside the box, or None if they do not overlap
+    ///
+    /// Where the polygon is not convex the result may contain zero-width spikes along the box edge.
+    pub fn clip(&self, rect: &LngLatBox) -> Option<Polygon> {
+        classify(self.clipped_area(rect), rect)?;
+        let mut buf = ClipBuffer::default();
+        let to_ring = |ring: &[(f64, f64)]| {
+            ring.iter()
+                .map(|&(vlng, vlat)| LngLat::new_raw(vlng, vlat))
+                .collect::<Vec<_>>()
+        };
+        let exterior = to_ring(buf.clip(&self.exterior, rect));
+        let interiors = self
+            .interiors
+            .iter()
+            .filter_map(|hole| {
+                let clipped = buf.clip(hole, rect);
+                (ring_area(clipped) != 0.0).then(|| to_ring(clipped))
+            })
+            .collect();
+        Some(Polygon::new(exterior, interiors))
+    }
+
+    /// Returns the area in m² on the GRS80 ellipsoid of the polygon inside the box
+    pub(crate) fn clipped_area_m2(&self, rect: &LngLatBox) -> f64 {
+        if !self.bounds.is_some_and(|b| overlaps(&b, rect)) {
+            return 0.0;
+        }
+        let mut buf = ClipBuffer::default();
+        let mut area = |ring: &[LngLat]| {
+            let clipped = buf.clip(ring, rect);
+            let degrees = clipped
+                .iter()
+                .map(|(vlng, vlat)| (vlng / MULTIPLYER, vlat / MULTIPLYER))
+                .collect::<Vec<_>>();
+            ellipsoid::ring_area(&degrees)
+        };
+        let exterior = area(&self.exterior);
+        if exterior == 0.0 {
+            return 0.0;
+        }
+        let holes: f64 = self.interiors.iter().map(|hole| area(hole)).sum();
+        (exterior - holes).max(0.0)
+    }
+
+    /// Returns the area in m² on the GRS80 ellipsoid
+    pub fn area_m2(&self) -> f64 {
+        self.bounds.map_or(0.0, |b| self.clipped_area_m2(&b))
+    }
+}
+
+fn open_ring(mut ring: Vec<LngLat>) -> Vec<LngLat> {
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    ring
+}
+
+/// A set of non-overlapping polygons
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MultiPolygon(pub Vec<Polygon>);
+
+impl MultiPolygon {
+    pub fn bounds(&self) -> Option<LngLatBox> {
+        self.0
+            .iter()
+            .filter_map(|p| p.bounds)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    pub(crate) fn clipped_area(&self, rect: &LngLatBox) -> f64 {
+        self.0.iter().map(|p| p.clipped_area(rect)).sum()
+    }
+
+    pub(crate) fn clipped_area_m2(&self, rect: &LngLatBox) -> f64 {
+        self.0.iter().map(|p| p.clipped_area_m2(rect)).sum()
+    }
+
+    /// Returns the parts of the polygons inside the box
+    pub fn clip(&self, rect: &LngLatBox) -> MultiPolygon {
+        MultiPolygon(self.0.iter().filter_map(|p| p.clip(rect)).collect())
+    }
+
+    /// Returns the area in m² on the GRS80 ellipsoid
+    pub fn area_m2(&self) -> f64 {
+        self.0.iter().map(|p| p.area_m2()).sum()
+    }
+}
+
+impl From<Polygon> for MultiPolygon {
+    fn from(polygon: Polygon) -> Self {
+        MultiPolygon(vec![polygon])
+    }
+}
+
+/// Tolerance used to absorb rounding errors when comparing areas, in squared internal units
+///
+/// It is a millionth of an eighth code (about 0.02 m²) at every level, so neither small
+/// polygons nor small holes are lost on coarse patches.
+const AREA_EPSILON: f64 = {
+    let d = Level::Eighth.divisions() as f64;
+    30. / d * (20. / d) * 1e-6
+};
+
+#[inline]
+fn classify(area: f64, rect: &LngLatBox) -> Option<Coverage> {
+    let rect_area = (rect.max.vlng - rect.min.vlng) * (rect.max.vlat - rect.min.vlat);
+    if area <= AREA_EPSILON {
+        None
+    } else if area >= rect_area - AREA_EPSILON {
+        Some(Coverage::Full)
+    } else {
+        Some(Coverage::Partial)
+    }
+}
+
+/// Patches merely touching the polygon do not intersect it
+impl Boundary for Polygon {
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        self.coverage(patch).is_some()
+    }
+
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        self.coverage(patch) == Some(Coverage::Full)
+    }
+
+    fn coverage(&self, patch: &LngLatBox) -> Option<Coverage> {
+        classify(self.clipped_area(patch), patch)
+    }
+}
+
+/// Patches merely touching the polygons do not intersect them
+impl Boundary for MultiPolygon {
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        self.coverage(patch).is_some()
+    }
+
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        self.coverage(patch) == Some(Coverage::Full)
+    }
+
+    fn coverage(&self, patch: &LngLatBox) -> Option<Coverage> {
+        classify(self.clipped_area(patch), patch)
+    }
+}
+
+/// Returns the codes of level `L` intersecting the polygons, classified as fully inside or partially intersecting
+pub fn polygon_coverage<L: MeshLevel>(
+    polygon: &MultiPolygon,
+) -> impl Iterator<Item = (L, Coverage)> {
+    let primaries = polygon.bounds().map(primaries_from_bounds);
+    primaries.into_iter().flatten().flat_map(move |prim| {
+        L::iter_within(prim, polygon).map(|(code, _, coverage)| (code, coverage))
+    })
+}
+
+/// Reusable buffers for clipping rings against boxes
+#[derive(Default)]
+pub(crate) struct ClipBuffer {
+    a: Vec<(f64, f64)>,
+    b: Vec<(f64, f64)>,
+}
+
+impl ClipBuffer {
+    /// Clips a ring against the box (Sutherland–Hodgman)
+    ///
+    /// The output may contain degenerate edges along the box boundary, which do not affect the area.
+    pub(crate) fn clip(&mut self, ring: &[LngLat], rect: &LngLatBox) -> &[(f64, f64)] {
+        self.a.clear();
+        self.a.extend(ring.iter().map(|p| (p.vlng, p.vlat)));
+        let (min, max) = (rect.min, rect.max);
+        clip_edge(
+            &self.a,
+            &mut self.b,
+            |p| p.0 >= min.vlng,
+            |p, q| lerp_x(p, q, min.vlng),
+        );
+        clip_edge(
+            &self.b,
+            &mut self.a,
+            |p| p.0 <= max.vlng,
+            |p, q| lerp_x(p, q, max.vlng),
+        );
+        clip_edge(
+            &self.a,
+            &mut self.b,
+            |p| p.1 >= min.vlat,
+            |p, q| lerp_y(p, q, min.vlat),
+        );
+        clip_edge(
+            &self.b,
+            &mut self.a,
+            |p| p.1 <= max.vlat,
+            |p, q| lerp_y(p, q, max.vlat),
+        );
+        &self.a
+    }
+}
+
+#[inline]
+fn clip_edge(
+    input: &[(f64, f64)],
+    output: &mut Vec<(f64, f64)>,
+    inside: impl Fn((f64, f64)) -> bool,
+    intersect: impl Fn((f64, f64), (f64, f64)) -> (f64, f64),
+) {
+    output.clear();
+    let Some(&last) = input.last() else {
+        return;
+    };
+    let mut prev = last;
+    let mut prev_inside = inside(prev);
+    for &cur in input {
+        let cur_inside = inside(cur);
+        if cur_inside {
+            if !prev_inside {
+                output.push(intersect(prev, cur));
+            }
+            output.push(cur);
+        } else if prev_inside {
+            output.push(intersect(prev, cur));
+        }
+        prev = cur;
+        prev_inside = cur_inside;
+    }
+}
+
+#[inline]
+fn lerp_x(p: (f64, f64), q: (f64, f64), x: f64) -> (f64, f64) {
+    (x, p.1 + (q.1 - p.1) * (x - p.0) / (q.0 - p.0))
+}
+
+#[inline]
+fn lerp_y(p: (f64, f64), q: (f64, f64), y: f64) -> (f64, f64) {
+    (p.0 + (q.0 - p.0) * (y - p.1) / (q.1 - p.1), y)
+}
+
+/// Signed area of a ring (shoelace formula), positive if counter-clockwise
+///
+/// Coordinates are taken relative to the first vertex, so rounding errors scale with the size
+/// of the ring rather than with its distance from the origin.
+pub(crate) fn ring_area(ring: &[(f64, f64)]) -> f64 {
+    let (Some(&origin), Some(&last)) = (ring.first(), ring.last()) else {
+        return 0.0;
+    };
+    let local = |p: (f64, f64)| (p.0 - origin.0, p.1 - origin.1);
+    let mut prev = local(last);
+    let mut sum = 0.0;
+    for &cur in ring {
+        let cur = local(cur);
+        sum += (prev.0 - cur.0) * (prev.1 + cur.1);
+        prev = cur;
+    }
+    sum / 2.0
+}
+
+#[cfg(feature = "geo-types")]
+mod geo_types_impl {
+    use super::*;
+
+    fn from_line_string(ls: &geo_types::LineString<f64>) -> Vec<LngLat> {
+        ls.coords().map(|c| LngLat::new(c.x, c.y)).collect()
+    }
+
+    fn to_line_string(ring: &[LngLat]) -> geo_types::LineString<f64> {
+        ring.iter()
+            .chain(ring.first())
+            .map(|p| geo_types::coord! { x: p.lng(), y: p.lat() })
+            .collect()
+    }
+
+    impl From<&geo_types::Polygon<f64>> for Polygon {
+        fn from(polygon: &geo_types::Polygon<f64>) -> Self {
+            Polygon::new(
+                from_line_string(polygon.exterior()),
+                polygon.interiors().iter().map(from_line_string).collect(),
+            )
+        }
+    }
+
+    impl From<&geo_types::MultiPolygon<f64>> for MultiPolygon {
+        fn from(mp: &geo_types::MultiPolygon<f64>) -> Self {
+            MultiPolygon(mp.iter().map(Polygon::from).collect())
+        }
+    }
+
+    impl From<&Polygon> for geo_types::Polygon<f64> {
+        fn from(polygon: &Polygon) -> Self {
+            geo_types::Polygon::new(
+                to_line_string(&polygon.exterior),
+                polygon
+                    .interiors
+                    .iter()
+                    .map(|r| to_line_string(r))
+                    .collect(),
+            )
+        }
+    }
+
+    impl From<&MultiPolygon> for geo_types::MultiPolygon<f64> {
+        fn from(mp: &MultiPolygon) -> Self {
+            geo_types::MultiPolygon(mp.0.iter().map(geo_types::Polygon::from).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_polygon() {
+        let secondary = SecondaryCode::from_str("644142").unwrap();
+        let polygon = Polygon::new(secondary.ring(1), vec![]);
+        assert_eq!(polygon.exterior().len(), 4);
+        assert_eq!(polygon.bounds(), Some(secondary.envelope()));
+
+        // a polygon equal to a secondary code
+        let cells = polygon_coverage::<StandardCode>(&polygon.clone().into()).collect::<Vec<_>>();
+        assert_eq!(cells.len(), 100);
+        assert!(cells.iter().all(|(_, c)| *c == Coverage::Full));
+        assert!(cells.iter().all(|(code, _)| code.secondary() == secondary));
+
+        // a hole of a single standard code
+        let hole = StandardCode::from_str("64414255").unwrap();
+        let polygon = Polygon::new(secondary.ring(1), vec![hole.ring(1)]);
+        let cells = polygon_coverage::<StandardCode>(&polygon.into()).collect::<Vec<_>>();
+        assert_eq!(cells.len(), 99);
+        assert!(
+            cells
+                .iter()
+                .all(|(code, c)| *code != hole && *c == Coverage::Full)
+        );
+    }
+
+    #[test]
+    fn test_small_areas() {
+        // a square of about 1 m² in the middle of an eighth code
+        let eighth = EighthCode::from_str("53394611111").unwrap();
+        let center = eighth.center();
+        let d = 5e-6;
+        let square = LngLatBox::new(
+            LngLat::new(center.lng() - d, center.lat() - d),
+            LngLat::new(center.lng() + d, center.lat() + d),
+        );
+        let small = MultiPolygon::from(Polygon::new(square.corners().to_vec(), vec![]));
+        let cells = polygon_coverage::<PrimaryCode>(&small).collect::<Vec<_>>();
+        assert_eq!(cells, [(eighth.primary(), Coverage::Partial)]);
+        let cells = polygon_coverage::<EighthCode>(&small).collect::<Vec<_>>();
+        assert_eq!(cells, [(eighth, Coverage::Partial)]);
+
+        // the same square as a hole keeps the codes containing it partial
+        let primary = eighth.primary();
+        let holed = MultiPolygon::from(Polygon::new(
+            primary.ring(1),
+            vec![square.corners().to_vec()],
+        ));
+        let cells = polygon_coverage::<PrimaryCode>(&holed).collect::<Vec<_>>();
+        assert_eq!(cells, [(primary, Coverage::Partial)]);
+        let partial = polygon_coverage::<EighthCode>(&holed)
+            .filter(|(_, c)| *c == Coverage::Partial)
+            .map(|(code, _)| code)
+            .collect::<Vec<_>>();
+        assert_eq!(partial, [eighth]);
+
+        // an empty exterior has no bounds and covers nothing
+        let empty = Polygon::new(vec![], vec![]);
+        assert_eq!(empty.bounds(), None);
+        assert_eq!(empty.area_m2(), 0.0);
+        assert_eq!(polygon_coverage::<PrimaryCode>(&empty.into()).count(), 0);
+    }
+
+    #[test]
+    fn test_triangle() {
+        // lower-right half of a secondary code, split along its diagonal
+        let envelope = SecondaryCode::from_str("644142").unwrap().envelope();
+        let triangle = Polygon::new(envelope.corners()[..3].to_vec(), vec![]);
+        let cells = polygon_coverage::<StandardCode>(&triangle.clone().into()).collect::<Vec<_>>();
+        let full = cells.iter().filter(|(_, c)| *c == Coverage::Full).count();
+        let partial = cells
+            .iter()
+            .filter(|(_, c)| *c == Coverage::Partial)
+            .count();
+        assert_eq!((full, partial), (45, 10));
+        for (code, coverage) in cells {
+            let (x3, y3) = (code.x3(), code.y3());
+            assert!(x3 >= y3);
+            assert_eq!(coverage == Coverage::Partial, x3 == y3);
+        }
+
+        // pruning must not change the result compared to testing every cell directly
+        let all = codes::<HalfCode>(primaries_in_land(), Some(envelope))
+            .filter(|c| triangle.intersects(&c.envelope()))
+            .count();
+        let multi = MultiPolygon::from(triangle);
+        assert_eq!(polygon_coverage::<HalfCode>(&multi).count(), all);
+    }
+
+    #[test]
+    fn test_multipolygon() {
+        let a = StandardCode::from_str("64414278").unwrap();
+        let b = StandardCode::from_str("53394611").unwrap();
+        let multi = MultiPolygon(vec![
+            Polygon::new(a.ring(1), vec![]),
+            Polygon::new(b.ring(1), vec![]),
+        ]);
+        let mut cells = polygon_coverage::<QuarterCode>(&multi)
+            .map(|(code, _)| code.standard())
+            .collect::<Vec<_>>();
+        assert_eq!(cells.len(), 32);
+        cells.dedup();
+        assert_eq!(cells.len(), 2);
+        assert!(MultiPolygon::default().bounds().is_none());
+        assert_eq!(
+            polygon_coverage::<QuarterCode>(&MultiPolygon::default()).count(),
+            0
+        );
+    }
+
+    #[cfg(feature = "geo-types")]
+    #[test]
+    fn test_geo_types() {
+        let a = StandardCode::from_str("64414278").unwrap();
+        let polygon = Polygon::new(a.ring(1), vec![]);
+        let geo = geo_types::Polygon::from(&polygon);
+        assert_eq!(geo.exterior().0.len(), 5);
+        assert_eq!(Polygon::from(&geo).exterior().len(), 4);
+        let multi = geo_types::MultiPolygon::from(&MultiPolygon::from(polygon));
+        assert_eq!(MultiPolygon::from(&multi).0.len(), 1);
+    }
+}
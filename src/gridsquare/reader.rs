@@ -0,0 +1,168 @@
+// Streaming reader for newline-delimited code files
+
+use super::LevelAndCode;
+use crate::Error;
+use std::io::BufRead;
+
+/// Lines longer than this cannot hold a code, and are rejected without being buffered
+pub const MAX_LINE_LENGTH: usize = 64;
+
+/// Errors from [`CodeReader`]
+#[derive(thiserror::Error, Debug)]
+pub enum ReadError {
+    #[error(transparent)]
+    Code(#[from] Error),
+    #[error("Line longer than {MAX_LINE_LENGTH} bytes")]
+    LineTooLong,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Reads one code per line from a [`BufRead`], skipping blank lines and accepting both LF and CRLF
+///
+/// Lines are parsed directly from the reader's internal buffer, so no allocation happens
+/// except for lines straddling a buffer boundary. Reading continues after an invalid or
+/// overlong line.
+pub struct CodeReader<R> {
+    reader: R,
+    pending: Vec<u8>,
+    /// Whether the current line is too long and is being skipped
+    overlong: bool,
+}
+
+impl<R: BufRead> CodeReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: Vec::with_capacity(16),
+            overlong: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[inline]
+fn parse_line(line: &[u8]) -> Option<Result<LevelAndCode, ReadError>> {
+    if line.len() > MAX_LINE_LENGTH {
+        return Some(Err(ReadError::LineTooLong));
+    }
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    (!line.is_empty()).then(|| LevelAndCode::from_ascii(line).map_err(ReadError::from))
+}
+
+impl<R: BufRead> CodeReader<R> {
+    /// Parses the buffered line, or reports it as too long
+    fn finish_line(&mut self) -> Option<Result<LevelAndCode, ReadError>> {
+        let result = if self.overlong {
+            Some(Err(ReadError::LineTooLong))
+        } else {
+            parse_line(&self.pending)
+        };
+        self.pending.clear();
+        self.overlong = false;
+        result
+    }
+}
+
+impl<R: BufRead> Iterator for CodeReader<R> {
+    type Item = Result<LevelAndCode, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let buf = match self.reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if buf.is_empty() {
+                // EOF: flush the last line without a trailing newline
+                return self.finish_line();
+            }
+            let Some(pos) = buf.iter().position(|&b| b == b'\n') else {
+                let len = buf.len();
+                if self.overlong || self.pending.len() + len > MAX_LINE_LENGTH {
+                    self.pending.clear();
+                    self.overlong = true;
+                } else {
+                    self.pending.extend_from_slice(buf);
+                }
+                self.reader.consume(len);
+                continue;
+            };
+            let result = if self.pending.is_empty() && !self.overlong {
+                parse_line(&buf[..pos])
+            } else {
+                if self.pending.len() + pos > MAX_LINE_LENGTH {
+                    self.overlong = true;
+                } else {
+                    self.pending.extend_from_slice(&buf[..pos]);
+                }
+                self.finish_line()
+            };
+            self.reader.consume(pos + 1);
+            if result.is_some() {
+                return result;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gridsquare::*;
+    use std::io::{BufReader, Cursor};
+
+    #[test]
+    fn test_code_reader() {
+        let input = b"6441\r\n644142\n\n64414278\r\n\r\n644142782\n6441427823\n64414278234";
+        let codes = CodeReader::new(Cursor::new(input))
+            .map(|c| c.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(codes.len(), 6);
+        assert!(matches!(codes[0], LevelAndCode::Primary(c) if c.to_string() == "6441"));
+        assert!(matches!(codes[1], LevelAndCode::Secondary(c) if c.to_string() == "644142"));
+        assert!(matches!(codes[2], LevelAndCode::Standard(c) if c.to_string() == "64414278"));
+        assert!(matches!(codes[3], LevelAndCode::Half(c) if c.to_string() == "644142782"));
+        assert!(matches!(codes[4], LevelAndCode::Quarter(c) if c.to_string() == "6441427823"));
+        assert!(matches!(codes[5], LevelAndCode::Eighth(c) if c.to_string() == "64414278234"));
+
+        // lines straddling the internal buffer
+        let reader = BufReader::with_capacity(3, Cursor::new(input));
+        assert_eq!(CodeReader::new(reader).filter(|c| c.is_ok()).count(), 6);
+
+        let mut reader = CodeReader::new(Cursor::new(b"6441\nfoo\n644188\n5339\n"));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ReadError::Code(Error::InvalidCode)))
+        ));
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ReadError::Code(Error::InvalidCode)))
+        ));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_overlong_lines() {
+        // a long line within the buffer, one straddling buffers, and one never terminated
+        let long = "1".repeat(MAX_LINE_LENGTH + 1);
+        let input = format!("{long}\n6441\n{long}{long}\n5339\n{}", "9".repeat(100_000));
+        for capacity in [3, 8192] {
+            let reader = BufReader::with_capacity(capacity, Cursor::new(input.as_bytes()));
+            let mut reader = CodeReader::new(reader);
+            assert!(matches!(reader.next(), Some(Err(ReadError::LineTooLong))));
+            assert!(reader.next().unwrap().is_ok());
+            assert!(matches!(reader.next(), Some(Err(ReadError::LineTooLong))));
+            assert!(reader.next().unwrap().is_ok());
+            assert!(matches!(reader.next(), Some(Err(ReadError::LineTooLong))));
+            assert!(reader.pending.capacity() <= MAX_LINE_LENGTH.max(16) * 2);
+            assert!(reader.next().is_none());
+        }
+    }
+}
@@ -0,0 +1,250 @@
+// Resampling values between levels
+
+use super::ellipsoid;
+use super::*;
+use std::collections::HashMap;
+
+/// How values are carried to another level by [`MeshMap::resample`] and [`MeshGrid::resample`]
+///
+/// Levels need not nest: codes of different levels are related by how much they overlap,
+/// so x5 and x2 codes can be converted to each other and to the quad chain alike.
+#[derive(Debug, Clone, Copy)]
+pub enum Resampling<'a> {
+    /// Extensive values (counts): split among the target codes in proportion to their overlap
+    /// on the grid (equally when the source is coarser), summed into coarser codes
+    Sum,
+    /// Intensive values (densities, rates): the mean of the overlapping source values weighted by
+    /// their overlap on the grid, so finer codes take the value of the code they are in
+    Mean,
+    /// Same as [`Resampling::Mean`] with overlaps measured as areas on the ellipsoid
+    AreaWeighted,
+    /// Extensive values split in proportion to the weights of the target codes (missing
+    /// weights count as 0), summed into coarser codes
+    ///
+    /// Values over target codes that all have a zero weight are split as with [`Resampling::Sum`],
+    /// so totals are always preserved.
+    Weighted(&'a MeshMap<f64>),
+}
+
+/// A cell of a grid with `divisions` cells along each axis of a primary code
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Cell {
+    pub divisions: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Resamples the values of the source cells to the codes of `level`, returning the values by index
+///
+/// Only target codes overlapping a source cell get a value.
+pub(crate) fn resample_cells(
+    sources: impl IntoIterator<Item = (Cell, f64)>,
+    level: Level,
+    method: Resampling,
+) -> HashMap<(u32, u32), f64> {
+    let divisions = level.divisions();
+    // sums of values and, for intensive methods, of the overlaps weighting them
+    let mut sums: HashMap<(u32, u32), (f64, f64)> = HashMap::new();
+    let with_area = matches!(method, Resampling::AreaWeighted);
+    let (mut targets, mut weights) = (Vec::new(), Vec::new());
+    for (cell, value) in sources {
+        targets.clear();
+        targets.extend(overlaps(cell, divisions, with_area));
+        match method {
+            Resampling::Sum => {
+                for (x, y, fraction, _) in &targets {
+                    sums.entry((*x, *y)).or_default().0 += value * fraction;
+                }
+            }
+            Resampling::Mean | Resampling::AreaWeighted => {
+                let scale = 1.0 / (cell.divisions as f64 * cell.divisions as f64);
+                for (x, y, fraction, area) in &targets {
+                    let weight = match method {
+                        Resampling::Mean => fraction * scale,
+                        _ => *area,
+                    };
+                    let sum = sums.entry((*x, *y)).or_default();
+                    sum.0 += value * weight;
+                    sum.1 += weight;
+                }
+            }
+            Resampling::Weighted(map) => {
+                weights.clear();
+                weights.extend(targets.iter().map(|(x, y, fraction, _)| {
+                    let code = LevelAndCode::from_index_xy(level, *x, *y).ok();
+                    code.and_then(|c| map.get(c)).copied().unwrap_or(0.0) * fraction
+                }));
+                let total = weights.iter().sum::<f64>();
+                for ((x, y, fraction, _), weight) in targets.iter().zip(&weights) {
+                    let share = if total > 0.0 {
+                        weight / total
+                    } else {
+                        *fraction
+                    };
+                    sums.entry((*x, *y)).or_default().0 += value * share;
+                }
+            }
+        }
+    }
+    sums.into_iter()
+        .map(|(index, (sum, weight))| match method {
+            Resampling::Mean | Resampling::AreaWeighted => (index, sum / weight),
+            Resampling::Sum | Resampling::Weighted(_) => (index, sum),
+        })
+        .collect()
+}
+
+/// Returns the cells of the grid with `divisions` cells per primary code overlapping the cell,
+/// with the fraction of the cell each covers and the area in m² of the overlap (0 unless
+/// `with_area`, as it takes much longer to compute)
+fn overlaps(
+    cell: Cell,
+    divisions: u32,
+    with_area: bool,
+) -> impl Iterator<Item = (u32, u32, f64, f64)> {
+    // a lattice fine enough for the edges of both grids
+    let lattice = lcm(cell.divisions as u64, divisions as u64);
+    let (s, t) = (lattice / cell.divisions as u64, lattice / divisions as u64);
+    let spans = move |i: u32| {
+        let (a, b) = (i as u64 * s, (i as u64 + 1) * s);
+        (a / t..=(b - 1) / t).map(move |j| (j as u32, (j * t).max(a)..b.min((j + 1) * t)))
+    };
+    let degrees = lattice as f64;
+    spans(cell.y).flat_map(move |(y, ys)| {
+        spans(cell.x).map(move |(x, xs)| {
+            let fraction = ((xs.end - xs.start) * (ys.end - ys.start)) as f64 / (s * s) as f64;
+            let area = if with_area {
+                ellipsoid::box_area(
+                    xs.start as f64 / degrees + 100.,
+                    ys.start as f64 / degrees * 2. / 3.,
+                    xs.end as f64 / degrees + 100.,
+                    ys.end as f64 / degrees * 2. / 3.,
+                )
+            } else {
+                0.0
+            };
+            (x, y, fraction, area)
+        })
+    })
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn code(s: &str) -> LevelAndCode {
+        LevelAndCode::from_str(s).unwrap()
+    }
+
+    fn total(map: &MeshMap<f64>) -> f64 {
+        map.iter().map(|(_, v)| v).sum()
+    }
+
+    #[test]
+    fn test_map_quad_chain() {
+        let map = [(code("53394611"), 100.0), (code("533946121"), 8.0)]
+            .into_iter()
+            .collect::<MeshMap<f64>>();
+
+        // disaggregation splits counts equally and copies densities
+        let halves = map.resample(Level::Half, Resampling::Sum);
+        assert_eq!(halves.len(), 5);
+        assert_eq!(halves.get(code("533946113")), Some(&25.0));
+        assert_eq!(halves.get(code("533946121")), Some(&8.0));
+        let halves = map.resample(Level::Half, Resampling::Mean);
+        assert_eq!(halves.get(code("533946113")), Some(&100.0));
+
+        // aggregation sums counts and averages densities over the covered part
+        let standards = map.resample(Level::Standard, Resampling::Sum);
+        assert_eq!(standards.get(code("53394612")), Some(&8.0));
+        let secondary = map.resample(Level::Secondary, Resampling::Mean);
+        assert_eq!(
+            secondary.get(code("533946")),
+            Some(&((100.0 * 4.0 + 8.0) / 5.0))
+        );
+        let secondary = map.resample(Level::Secondary, Resampling::AreaWeighted);
+        let value = secondary.get(code("533946")).unwrap();
+        assert!((value - (100.0 * 4.0 + 8.0) / 5.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_map_weighted() {
+        let map = [(code("53394611"), 100.0)]
+            .into_iter()
+            .collect::<MeshMap<f64>>();
+        let weights = [(code("533946111"), 1.0), (code("533946112"), 3.0)]
+            .into_iter()
+            .collect::<MeshMap<f64>>();
+        let halves = map.resample(Level::Half, Resampling::Weighted(&weights));
+        assert_eq!(halves.get(code("533946111")), Some(&25.0));
+        assert_eq!(halves.get(code("533946112")), Some(&75.0));
+        assert_eq!(halves.get(code("533946113")), Some(&0.0));
+
+        // without weights the value is split equally
+        let halves = map.resample(Level::Half, Resampling::Weighted(&MeshMap::new()));
+        assert_eq!(halves.get(code("533946114")), Some(&25.0));
+    }
+
+    #[test]
+    fn test_map_integrated() {
+        let map = [(code("5339461"), 50.0)]
+            .into_iter()
+            .collect::<MeshMap<f64>>();
+
+        // x5 to x2: the x2 codes along the edge are partly covered
+        let x2 = map.resample(Level::X2, Resampling::Sum);
+        assert_eq!(x2.len(), 9);
+        assert!((total(&x2) - 50.0).abs() < 1e-9);
+        assert_eq!(x2.get(code("533946005")), Some(&8.0));
+        assert_eq!(x2.get(code("533946045")), Some(&4.0));
+        assert_eq!(x2.get(code("533946445")), Some(&2.0));
+        let x2 = map.resample(Level::X2, Resampling::Mean);
+        assert_eq!(x2.get(code("533946445")), Some(&50.0));
+
+        // standards to x5 and back
+        let standards = map.resample(Level::Standard, Resampling::Sum);
+        assert_eq!(standards.len(), 25);
+        assert!(standards.iter().all(|(_, v)| *v == 2.0));
+        let x5 = standards.resample(Level::X5, Resampling::Sum);
+        assert_eq!(x5.iter().collect::<Vec<_>>(), [(code("5339461"), &50.0)]);
+    }
+
+    #[test]
+    fn test_grid() {
+        let sw = StandardCode::from_str("53394600").unwrap().center();
+        let ne = StandardCode::from_str("53394699").unwrap().center();
+        let bounds = LngLatBox::new(sw, ne);
+        let ones = MeshGrid::<StandardCode, f64>::new(bounds, 1.0);
+        assert_eq!(ones.len(), 100);
+
+        let x5 = ones.resample::<X5Code>(Resampling::Sum);
+        assert_eq!((x5.width(), x5.height()), (2, 2));
+        assert!(x5.values().iter().all(|v| *v == 25.0));
+        let x2 = ones.resample::<X2Code>(Resampling::Sum);
+        assert_eq!((x2.width(), x2.height()), (5, 5));
+        assert!(x2.values().iter().all(|v| *v == 4.0));
+        assert_eq!(x2.bounds(), ones.bounds());
+
+        // x2 to x5 crosses edges that do not line up, and still preserves totals
+        let x5 = x2.resample::<X5Code>(Resampling::Sum);
+        assert!(x5.values().iter().all(|v| (v - 25.0).abs() < 1e-9));
+        let halves = x5.resample::<HalfCode>(Resampling::Mean);
+        assert_eq!(halves.len(), 400);
+        assert!(halves.values().iter().all(|v| (v - 25.0).abs() < 1e-9));
+
+        // each x2 code averages two columns of standards
+        let columns = MeshGrid::<StandardCode, u32>::from_fn(bounds, |c| c.x3() as u32);
+        let grid = columns.resample::<X2Code>(Resampling::AreaWeighted);
+        let value = grid.get(X2Code::from_str("533946025").unwrap()).unwrap();
+        assert!((value - 2.5).abs() < 1e-9);
+    }
+}
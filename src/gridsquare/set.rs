@@ -0,0 +1,390 @@
+// Mixed-level sets of codes
+
+use super::cover::merge_siblings;
+use super::*;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+/// A set of codes of mixed levels, kept normalized
+///
+/// Codes covered by another member are dropped and complete sets of siblings are merged
+/// into their parent (four halves into a standard, 10×10 standards into a secondary, and
+/// so on), so each region has a single representation. Integrated (x5 and x2) codes are
+/// stored as the standard codes they consist of.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MeshSet {
+    codes: BTreeSet<LevelAndCode>,
+}
+
+impl MeshSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of member codes (after normalization)
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Returns the member codes in code order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = LevelAndCode> + '_ {
+        self.codes.iter().copied()
+    }
+
+    /// Adds the code, returning false if it was already covered by the set
+    pub fn insert(&mut self, code: impl Into<LevelAndCode>) -> bool {
+        let code = code.into();
+        if code.level().is_integrated() {
+            return quad_chain(code)
+                .into_iter()
+                .fold(false, |inserted, c| self.insert(c) | inserted);
+        }
+        if self.contains(code) {
+            return false;
+        }
+        let covered = self.descendants(code).collect::<Vec<_>>();
+        for c in covered {
+            self.codes.remove(&c);
+        }
+        self.codes.insert(code);
+
+        let mut code = code;
+        while let Some(parent) = code.parent() {
+            if !parent.children().all(|c| self.codes.contains(&c)) {
+                break;
+            }
+            for c in parent.children() {
+                self.codes.remove(&c);
+            }
+            self.codes.insert(parent);
+            code = parent;
+        }
+        true
+    }
+
+    /// Returns true if the code is entirely covered by the set
+    pub fn contains(&self, code: impl Into<LevelAndCode>) -> bool {
+        let code = code.into();
+        if code.level().is_integrated() {
+            return quad_chain(code).into_iter().all(|c| self.contains(c));
+        }
+        Level::ALL
+            .iter()
+            .take_while(|level| **level <= code.level())
+            .filter_map(|level| code.ancestor(*level))
+            .any(|c| self.codes.contains(&c))
+    }
+
+    /// Returns true if the point is in one of the member codes
+    pub fn contains_point(&self, lnglat: LngLat) -> bool {
+        LevelAndCode::from_lnglat(Level::Eighth, lnglat).is_ok_and(|code| self.contains(code))
+    }
+
+    pub fn union(&self, other: &MeshSet) -> MeshSet {
+        self.iter().chain(other.iter()).collect()
+    }
+
+    pub fn intersection(&self, other: &MeshSet) -> MeshSet {
+        // members of one set are either disjoint from or nested in members of the other
+        self.iter()
+            .filter(|c| other.contains(*c))
+            .chain(other.iter().filter(|c| self.contains(*c)))
+            .collect()
+    }
+
+    pub fn difference(&self, other: &MeshSet) -> MeshSet {
+        let mut codes = BTreeSet::new();
+        let mut stack = self.iter().rev().collect::<Vec<_>>();
+        while let Some(code) = stack.pop() {
+            if other.contains(code) {
+                continue;
+            }
+            if other.descendants(code).next().is_some() {
+                stack.extend(code.children().collect::<Vec<_>>().into_iter().rev());
+            } else {
+                codes.insert(code);
+            }
+        }
+        MeshSet { codes }
+    }
+
+    /// Returns the codes of the given level covering the set, in code order
+    ///
+    /// Coarser members are expanded into their descendants; finer members are replaced by
+    /// their ancestor, so the result covers the set but may extend beyond it.
+    pub fn expand(&self, level: Level) -> Vec<LevelAndCode> {
+        let mut result: Vec<LevelAndCode> = Vec::new();
+        for code in self.iter() {
+            match code.ancestor(level) {
+                Some(ancestor) => {
+                    if result.last() != Some(&ancestor) {
+                        result.push(ancestor);
+                    }
+                }
+                None => push_descendants(code, level, &mut result),
+            }
+        }
+        if level.is_integrated() {
+            // standards of an integrated code are not contiguous in code order
+            result.sort_unstable();
+            result.dedup();
+        }
+        result
+    }
+
+    /// Returns the codes of level `L` covering the set, in code order (see [`Self::expand`])
+    pub fn to_level<L: MeshLevel>(&self) -> Vec<L> {
+        self.expand(L::LEVEL)
+            .into_iter()
+            .map(|c| {
+                let (x, y) = c.index_xy();
+                L::from_index_xy(x, y).expect("index is within the domain")
+            })
+            .collect()
+    }
+
+    /// Returns the members strictly inside the code
+    fn descendants(&self, code: LevelAndCode) -> impl Iterator<Item = LevelAndCode> + '_ {
+        self.codes
+            .range((Bound::Excluded(code), Bound::Unbounded))
+            .take_while(move |c| code.contains(c))
+            .copied()
+    }
+}
+
+fn push_descendants(code: LevelAndCode, level: Level, out: &mut Vec<LevelAndCode>) {
+    if code.level() >= level {
+        out.push(code);
+    } else if level.is_integrated() && code.level() == Level::Secondary {
+        let split = level.split();
+        let (x, y) = code.index_xy();
+        out.extend((0..split * split).map(|i| {
+            LevelAndCode::from_index_xy(level, x * split + i % split, y * split + i / split)
+                .expect("children are within the domain")
+        }));
+    } else {
+        for child in code.children() {
+            push_descendants(child, level, out);
+        }
+    }
+}
+
+/// Returns the code itself, or the standard codes making up an integrated code
+fn quad_chain(code: LevelAndCode) -> Vec<LevelAndCode> {
+    match code {
+        LevelAndCode::X5(c) => c.iter_standard().map(Into::into).collect(),
+        LevelAndCode::X2(c) => c.iter_standard().map(Into::into).collect(),
+        _ => vec![code],
+    }
+}
+
+impl<C: Into<LevelAndCode>> FromIterator<C> for MeshSet {
+    fn from_iter<I: IntoIterator<Item = C>>(iter: I) -> Self {
+        // dropping covered codes is a linear scan, since descendants follow their ancestors
+        let mut kept: Vec<LevelAndCode> = Vec::new();
+        let codes = iter.into_iter().map(Into::into).flat_map(quad_chain);
+        for code in codes.collect::<BTreeSet<_>>() {
+            if kept.last().is_none_or(|last| !last.contains(&code)) {
+                kept.push(code);
+            }
+        }
+        let mut codes = kept.into_iter().collect();
+        merge_siblings(&mut codes, Level::Primary);
+        MeshSet { codes }
+    }
+}
+
+impl<C: Into<LevelAndCode>> Extend<C> for MeshSet {
+    fn extend<I: IntoIterator<Item = C>>(&mut self, iter: I) {
+        for code in iter {
+            self.insert(code);
+        }
+    }
+}
+
+/// The union of the member codes; patches merely touching the edge of the set do not intersect it
+///
+/// A patch is reported as contained only when a single member contains it.
+impl Boundary for MeshSet {
+    fn intersects(&self, patch: &LngLatBox) -> bool {
+        self.candidates(patch).any(|c| c.intersects(patch))
+    }
+
+    fn contains(&self, patch: &LngLatBox) -> bool {
+        self.candidates(patch)
+            .any(|c| Boundary::contains(&c, patch))
+    }
+}
+
+impl MeshSet {
+    /// Returns the members within the primaries around the patch
+    fn candidates(&self, patch: &LngLatBox) -> impl Iterator<Item = LevelAndCode> + '_ {
+        primaries_from_bounds(*patch).flat_map(|primary| {
+            let primary = LevelAndCode::from(primary);
+            self.codes
+                .range(primary..)
+                .take_while(move |c| primary.contains(c))
+                .copied()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn code(s: &str) -> LevelAndCode {
+        LevelAndCode::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_normalization() {
+        let standard = code("53394611");
+        let mut set = standard
+            .children()
+            .chain([code("533946111"), code("5339461114"), code("64414278")])
+            .collect::<MeshSet>();
+        assert_eq!(set.iter().collect::<Vec<_>>(), [standard, code("64414278")]);
+
+        // ten by ten standards merge into a secondary, and so on upward
+        let secondary = code("533946");
+        assert!(!set.insert(code("53394611123")));
+        for c in secondary.children().filter(|c| *c != standard) {
+            assert!(set.insert(c));
+        }
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            [secondary, code("64414278")]
+        );
+        assert_eq!(set.len(), 2);
+
+        let mut set = MeshSet::new();
+        set.extend(code("64414278").children());
+        assert_eq!(set.iter().collect::<Vec<_>>(), [code("64414278")]);
+        set.insert(code("6441"));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [code("6441")]);
+    }
+
+    #[test]
+    fn test_contains() {
+        let set = [code("53394611"), code("644142781")]
+            .into_iter()
+            .collect::<MeshSet>();
+        assert!(set.contains(code("53394611")));
+        assert!(set.contains(code("53394611432")));
+        assert!(set.contains(StandardCode::from_str("53394611").unwrap()));
+        assert!(!set.contains(code("533946")));
+        assert!(!set.contains(code("64414278")));
+        assert!(set.contains(code("6441427813")));
+
+        let inside = code("53394611").center();
+        assert!(set.contains_point(inside));
+        assert!(!set.contains_point(code("53394612").center()));
+        assert!(!set.contains_point(LngLat::new(0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_boolean_operations() {
+        let a = [code("53394611"), code("53394612")]
+            .into_iter()
+            .collect::<MeshSet>();
+        let b = [code("533946121"), code("53394613"), code("5339461111")]
+            .into_iter()
+            .collect::<MeshSet>();
+
+        let union = a.union(&b);
+        assert_eq!(
+            union.iter().collect::<Vec<_>>(),
+            [code("53394611"), code("53394612"), code("53394613")]
+        );
+        let intersection = a.intersection(&b);
+        assert_eq!(
+            intersection.iter().collect::<Vec<_>>(),
+            [code("5339461111"), code("533946121")]
+        );
+        assert_eq!(b.intersection(&a), intersection);
+
+        let difference = a.difference(&b);
+        assert_eq!(
+            difference.iter().collect::<Vec<_>>(),
+            [
+                code("5339461112"),
+                code("5339461113"),
+                code("5339461114"),
+                code("533946112"),
+                code("533946113"),
+                code("533946114"),
+                code("533946122"),
+                code("533946123"),
+                code("533946124"),
+            ]
+        );
+        assert_eq!(difference.union(&intersection), a);
+        assert!(difference.intersection(&b).is_empty());
+        assert_eq!(
+            b.difference(&a).iter().collect::<Vec<_>>(),
+            [code("53394613")]
+        );
+        assert!(a.difference(&a).is_empty());
+    }
+
+    #[test]
+    fn test_expand() {
+        let set = [code("53394611"), code("5339461234")]
+            .into_iter()
+            .collect::<MeshSet>();
+        let halves = set.to_level::<HalfCode>();
+        assert_eq!(halves.len(), 5);
+        assert!(halves.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(halves[4].to_string(), "533946123");
+        let standards = set.expand(Level::Standard);
+        assert_eq!(standards, [code("53394611"), code("53394612")]);
+        assert_eq!(set.expand(Level::Eighth).len(), 64 + 4);
+    }
+
+    #[test]
+    fn test_integrated() {
+        // integrated codes are stored as their standards, and merge like them
+        let mut set = [code("5339461"), code("533946005")]
+            .into_iter()
+            .collect::<MeshSet>();
+        assert_eq!(set.len(), 25);
+        assert!(set.contains(code("5339461")));
+        assert!(set.contains(code("533946005")));
+        assert!(!set.contains(code("533946045")));
+        for c in ["5339462", "5339463", "5339464"] {
+            assert!(set.insert(code(c)));
+        }
+        assert!(!set.insert(code("533946885")));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [code("533946")]);
+
+        let set = [code("53394611"), code("53394655")]
+            .into_iter()
+            .collect::<MeshSet>();
+        let x5 = set.to_level::<X5Code>();
+        assert_eq!(
+            x5.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            ["5339461", "5339464"]
+        );
+        assert_eq!(set.expand(Level::X2).len(), 2);
+        let secondary = [code("533946")].into_iter().collect::<MeshSet>();
+        assert_eq!(secondary.to_level::<X2Code>().len(), 25);
+        assert!(secondary.expand(Level::X5).is_sorted());
+    }
+
+    #[test]
+    fn test_boundary() {
+        let set = [code("53394611"), code("644142781")]
+            .into_iter()
+            .collect::<MeshSet>();
+        let halves = codes::<HalfCode>(primaries_in_land(), &set).collect::<Vec<_>>();
+        assert_eq!(halves.len(), 5);
+        let eighths = patches::<EighthCode>(primaries_in_land(), &set).count();
+        assert_eq!(eighths, 64 + 16);
+    }
+}